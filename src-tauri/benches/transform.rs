@@ -0,0 +1,186 @@
+use std::sync::atomic::AtomicBool;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use scrap_native_lib::{
+    cache::Cache,
+    compilers::Compilers,
+    options::{TransformOptions, Watchdog},
+    project::{compile_project, Entity},
+    swc_utils::transform,
+};
+use swc::Compiler;
+use swc_core::common::{sync::Lrc, FilePathMapping, SourceMap};
+
+/// Sprite script with `functions` functions, each with
+/// the loops, setters and engine calls a game would have
+fn script(functions: usize) -> String {
+    let mut code = String::from(
+        "interface Variables {\n\tscore: number;\n\tlives: number;\n\tnames: string[];\n}\n\n",
+    );
+
+    for i in 0..functions {
+        code += &format!(
+            r#"function patrol{i}(steps: number, speed: number): number {{
+	let travelled = 0;
+	for (let step = 0; step < steps; step++) {{
+		self.move(speed);
+		self.x += speed / 2;
+		if (self.isTouchingEdge()) {{
+			self.turnRight(180);
+			self.variables.score++;
+		}} else if (self.isTouching($["Enemy"])) {{
+			self.variables.lives -= 1;
+			self.say(`Ouch ${{self.variables.lives}}`);
+		}}
+		travelled += speed;
+	}}
+	const visible = self.variables.names.filter(name => name.length > {i} % 5);
+	return travelled + visible.length;
+}}
+
+self.whenReceiveMessage("level {i}", () => {{
+	let count = 0;
+	while (count < 10) {{
+		count += patrol{i}(count, 2) > 5 ? 1 : 2;
+		self.wait(0.1);
+	}}
+	self.effects.color = count * 10;
+}});
+
+"#
+        );
+    }
+
+    code += "self.whenFlag(() => {\n\tself.goTo(0, 0);\n\tself.show();\n});\n";
+    return code;
+}
+
+const STAGE: &str = r#"interface Variables {
+	score: number;
+}
+
+self.whenFlag(() => {
+	self.variables.score = 0;
+	for (let level = 0; level < 5; level++) {
+		self.broadcastMessageWait(`level ${level}`);
+	}
+});
+"#;
+
+fn instrumented() -> TransformOptions {
+    return TransformOptions {
+        watchdog: Some(Watchdog::default()),
+        debug: true,
+        highlight_blocks: true,
+        profile: true,
+        coverage: true,
+        ..Default::default()
+    };
+}
+
+/// `fresh` is the baseline of `pooled`: a new compiler for every file
+/// against one reused from [`Compilers`]. `instrumented` is `pooled`
+/// with every debugging option on.
+fn transforming(c: &mut Criterion) {
+    let mut group = c.benchmark_group("transform");
+    let compilers = Compilers::default();
+
+    for functions in [10, 100, 500] {
+        let code = script(functions);
+        group.throughput(Throughput::Bytes(code.len() as u64));
+
+        group.bench_with_input(BenchmarkId::new("fresh", functions), &code, |b, code| {
+            b.iter(|| {
+                let compiler = Compiler::new(Lrc::new(SourceMap::new(FilePathMapping::empty())));
+                transform(&compiler, code.clone(), Default::default()).unwrap()
+            });
+        });
+
+        group.bench_with_input(BenchmarkId::new("pooled", functions), &code, |b, code| {
+            b.iter(|| {
+                compilers
+                    .with(|compiler| transform(compiler, code.clone(), Default::default()).unwrap())
+            });
+        });
+
+        group.bench_with_input(
+            BenchmarkId::new("instrumented", functions),
+            &code,
+            |b, code| {
+                b.iter(|| {
+                    compilers
+                        .with(|compiler| transform(compiler, code.clone(), instrumented()).unwrap())
+                });
+            },
+        );
+    }
+
+    group.finish();
+}
+
+fn entities(sprites: usize) -> Vec<Entity> {
+    let mut entities = vec![Entity {
+        name: "Stage".to_string(),
+        code: STAGE.to_string(),
+        stage: true,
+        init: Default::default(),
+        current: 0,
+        images: vec![("backdrop".to_string(), "Stage/backdrop.svg".to_string())],
+        sounds: vec![],
+    }];
+
+    for i in 0..sprites {
+        entities.push(Entity {
+            name: format!("Sprite{}", i),
+            code: script(20),
+            stage: false,
+            init: Default::default(),
+            current: 0,
+            images: vec![("costume".to_string(), format!("Sprite{}/costume.svg", i))],
+            sounds: vec![("pop".to_string(), format!("Sprite{}/pop.mp3", i))],
+        });
+    }
+
+    return entities;
+}
+
+/// `cold` is the baseline of `one changed`, which gets
+/// every entity but one from the cache.
+fn bundling(c: &mut Criterion) {
+    let mut group = c.benchmark_group("compile_project");
+    let compilers = Compilers::default();
+    let cached = Cache::default();
+    let running = AtomicBool::new(false);
+
+    group.bench_function("cold", |b| {
+        b.iter(|| {
+            let cache = Cache::default();
+            compile_project(
+                entities(30),
+                Default::default(),
+                &cache,
+                &compilers,
+                &running,
+            )
+            .unwrap()
+        });
+    });
+
+    // Only one sprite changed since the last preview.
+    group.bench_function("one changed", |b| {
+        let mut generation = 0;
+
+        b.iter(|| {
+            let mut entities = entities(30);
+            entities[1].code += &format!("// {}\n", generation);
+            generation += 1;
+
+            compile_project(entities, Default::default(), &cached, &compilers, &running).unwrap()
+        });
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, transforming, bundling);
+criterion_main!(benches);
@@ -1,3 +1,198 @@
+use std::{collections::BTreeMap, env, fs, path::Path};
+
+const STATIC: &str = "../src/monaco-editor/lib/static.d.ts";
+
+/// Sources of the compiler, whose changes invalidate what it compiled
+const SOURCES: &str = "src";
+
+/// Collects the `@setter` tags of the engine API into `setters.json`,
+/// which maps writable properties to the methods setting them and
+/// whether the methods take the property name, e.g. `@setter setX(name, value)`.
+fn setters(source: &str) -> String {
+    let mut setters: Vec<String> = vec![];
+    let mut pending: Option<(String, bool)> = None;
+    let mut comment = false;
+
+    for line in source.lines() {
+        let line = line.trim();
+
+        if line.starts_with("/**") {
+            comment = true;
+        }
+
+        if comment {
+            if let Some(index) = line.find("@setter") {
+                let tag = line[index + "@setter".len()..].trim();
+
+                let (method, arguments) = match tag.split_once('(') {
+                    Some((method, arguments)) => (method.trim(), arguments),
+                    None => panic!("@setter {} doesn't list its arguments", tag),
+                };
+
+                let named = match arguments.replace(' ', "").as_str() {
+                    "name,value)" => true,
+                    "value)" => false,
+                    _ => panic!(
+                        "@setter {} must take (name, value) or (value): {}",
+                        method, line
+                    ),
+                };
+
+                pending = Some((method.to_string(), named));
+            }
+
+            if line.ends_with("*/") {
+                comment = false;
+            }
+
+            continue;
+        }
+
+        if line.is_empty() {
+            continue;
+        }
+
+        let (method, named) = match pending.take() {
+            Some(setter) => setter,
+            None => continue,
+        };
+
+        if line.starts_with("readonly ") {
+            panic!(
+                "@setter {} is attached to a readonly property: {}",
+                method, line
+            );
+        }
+
+        let name: String = line
+            .chars()
+            .take_while(|c| c.is_alphanumeric() || *c == '_' || *c == '$')
+            .collect();
+
+        match line[name.len()..].trim_start_matches('?').chars().next() {
+            Some(':') if !name.is_empty() => {
+                setters.push(format!(
+                    "\"{}\":{{\"method\":\"{}\",\"named\":{}}}",
+                    name, method, named
+                ));
+            }
+            _ => panic!("@setter {} is not attached to a property: {}", method, line),
+        }
+    }
+
+    let out = env::var("OUT_DIR").unwrap();
+    let json = format!("{{{}}}", setters.join(","));
+
+    fs::write(Path::new(&out).join("setters.json"), &json).unwrap();
+
+    return json;
+}
+
+/// Collects the methods and functions of the API into `methods.json`,
+/// which tells whether they have the `@async` tag.
+fn methods(source: &str) -> String {
+    let mut methods: BTreeMap<String, bool> = BTreeMap::new();
+    let mut pending = false;
+    let mut comment = false;
+
+    for line in source.lines() {
+        let line = line.trim();
+
+        if line.starts_with("/**") {
+            comment = true;
+        }
+
+        if comment {
+            pending |= line.contains("@async");
+
+            if line.ends_with("*/") {
+                comment = false;
+            }
+
+            continue;
+        }
+
+        if line.is_empty() || line.starts_with("//") {
+            continue;
+        }
+
+        let asynchronous = std::mem::take(&mut pending);
+        let line = line.strip_prefix("declare function ").unwrap_or(line);
+
+        let name: String = line
+            .chars()
+            .take_while(|c| c.is_alphanumeric() || *c == '_' || *c == '$')
+            .collect();
+
+        match line[name.len()..].chars().next() {
+            // `new(...)` is a construct signature.
+            Some('(' | '<') if !name.is_empty() && name != "new" => {
+                *methods.entry(name).or_default() |= asynchronous;
+            }
+            _ if asynchronous => panic!("@async is not attached to a method: {}", line),
+            _ => {}
+        }
+    }
+
+    let out = env::var("OUT_DIR").unwrap();
+    let json = format!(
+        "{{{}}}",
+        methods
+            .iter()
+            .map(|(name, asynchronous)| format!("\"{}\":{}", name, asynchronous))
+            .collect::<Vec<_>>()
+            .join(",")
+    );
+
+    fs::write(Path::new(&out).join("methods.json"), &json).unwrap();
+
+    return json;
+}
+
+/// Sets `COMPILER_VERSION` to the package version and an FNV-1a hash
+/// of everything the compiled code depends on: the sources of the
+/// compiler, the locked dependencies, e.g. swc, and the engine API.
+fn fingerprint(inputs: &[&str]) {
+    let mut files: Vec<_> = fs::read_dir(SOURCES)
+        .expect("Compiler sources not found")
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    files.sort();
+    files.push(Path::new("Cargo.lock").to_path_buf());
+
+    let mut hash: u64 = 0xcbf29ce484222325;
+    let mut add = |bytes: &[u8]| {
+        // The zero separates the inputs.
+        for byte in bytes.iter().chain(&[0]) {
+            hash ^= *byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    };
+
+    for file in files {
+        add(&fs::read(file).unwrap_or_default());
+    }
+
+    for input in inputs {
+        add(input.as_bytes());
+    }
+
+    println!(
+        "cargo:rustc-env=COMPILER_VERSION={}-{:016x}",
+        env::var("CARGO_PKG_VERSION").unwrap(),
+        hash
+    );
+}
+
 fn main() {
+    println!("cargo:rerun-if-changed={}", STATIC);
+    println!("cargo:rerun-if-changed={}", SOURCES);
+    println!("cargo:rerun-if-changed=Cargo.lock");
+
+    let source = fs::read_to_string(STATIC).expect("Engine API declarations not found");
+
+    let setters = setters(&source);
+    let methods = methods(&source);
+    fingerprint(&[&source, &setters, &methods]);
     tauri_build::build()
 }
@@ -0,0 +1,292 @@
+use std::{collections::HashMap, fs, path::PathBuf, sync::Mutex};
+
+use serde::{Deserialize, Serialize};
+
+use crate::options::TransformOptions;
+use crate::swc_utils::Transformed;
+
+/// Entries kept before the least recently used ones get dropped
+const CAPACITY: usize = 256;
+
+/// Version and fingerprint of the compiler, see `build.rs`.
+/// A file persisted by another one gets discarded.
+const VERSION: &str = env!("COMPILER_VERSION");
+
+#[derive(Serialize, Deserialize)]
+struct Persisted<E> {
+    version: String,
+    entries: Vec<E>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    code: String,
+    /// Serialized transform options
+    options: String,
+    transformed: Transformed,
+    /// When the entry was last used, for the eviction
+    used: u64,
+}
+
+#[derive(Default)]
+struct Entries {
+    entries: HashMap<u64, Entry>,
+    clock: u64,
+    /// Whether there are changes to persist
+    dirty: bool,
+}
+
+/// Compiled entities by source hash and transform options,
+/// so that unchanged entities don't get compiled again
+#[derive(Default)]
+pub struct Cache {
+    entries: Mutex<Entries>,
+    /// File the cache persists to, in memory only if unset
+    path: Option<PathBuf>,
+}
+
+/// FNV-1a, which unlike `DefaultHasher` is the same in every build
+fn key(code: &str, options: &str) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+
+    // The zero separates the code from the options.
+    for byte in code.bytes().chain([0]).chain(options.bytes()) {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+
+    return hash;
+}
+
+impl Cache {
+    /// Loads the cache persisted to `path`, if any. A missing or unreadable
+    /// file, or one of another version, starts an empty cache.
+    pub fn new(path: Option<PathBuf>) -> Cache {
+        let entries: Vec<Entry> = path
+            .as_ref()
+            .and_then(|path| fs::read(path).ok())
+            .and_then(|bytes| serde_json::from_slice::<Persisted<Entry>>(&bytes).ok())
+            .filter(|persisted| persisted.version == VERSION)
+            .map(|persisted| persisted.entries)
+            .unwrap_or_default();
+
+        let clock = entries.iter().map(|entry| entry.used).max().unwrap_or(0);
+
+        let entries = entries
+            .into_iter()
+            .map(|entry| (key(&entry.code, &entry.options), entry))
+            .collect();
+
+        return Cache {
+            entries: Mutex::new(Entries {
+                entries,
+                clock,
+                dirty: false,
+            }),
+            path,
+        };
+    }
+
+    pub fn get(&self, code: &str, options: &TransformOptions) -> Option<Transformed> {
+        let options = serde_json::to_string(options).unwrap();
+        let mut entries = self.entries.lock().unwrap();
+
+        entries.clock += 1;
+        let clock = entries.clock;
+
+        // The code and the options are compared too, in case of a collision.
+        return match entries.entries.get_mut(&key(code, &options)) {
+            Some(entry) if entry.code == code && entry.options == options => {
+                entry.used = clock;
+                Some(entry.transformed.clone())
+            }
+            _ => None,
+        };
+    }
+
+    pub fn insert(&self, code: &str, options: &TransformOptions, transformed: Transformed) {
+        let options = serde_json::to_string(options).unwrap();
+        let mut entries = self.entries.lock().unwrap();
+
+        entries.clock += 1;
+        entries.dirty = true;
+
+        let entry = Entry {
+            code: code.to_string(),
+            used: entries.clock,
+            options,
+            transformed,
+        };
+
+        entries
+            .entries
+            .insert(key(&entry.code, &entry.options), entry);
+
+        if entries.entries.len() > CAPACITY {
+            let oldest = entries
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.used)
+                .map(|(key, _)| *key);
+
+            if let Some(oldest) = oldest {
+                entries.entries.remove(&oldest);
+            }
+        }
+    }
+
+    /// Writes the cache to its file, if it has one and changed.
+    /// The cache is only an optimization, so failing to write it is ignored.
+    pub fn persist(&self) {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return,
+        };
+
+        let mut entries = self.entries.lock().unwrap();
+
+        if !std::mem::take(&mut entries.dirty) {
+            return;
+        }
+
+        let persisted = Persisted {
+            version: VERSION.to_string(),
+            entries: entries.entries.values().collect(),
+        };
+
+        if let Ok(json) = serde_json::to_vec(&persisted) {
+            if let Some(dir) = path.parent() {
+                let _ = fs::create_dir_all(dir);
+            }
+
+            let _ = fs::write(path, json);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, path::PathBuf, process};
+
+    use super::{Cache, Entry, Persisted, CAPACITY};
+    use crate::options::TransformOptions;
+    use crate::swc_utils::Transformed;
+
+    fn transformed(code: &str) -> Transformed {
+        return Transformed {
+            code: code.to_string(),
+            map: None,
+            diagnostics: vec![],
+            probes: vec![],
+            sites: vec![],
+            counters: vec![],
+        };
+    }
+
+    /// Cache file of the test, removed first
+    fn file(name: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!("scrap-{}-{}.json", name, process::id()));
+        let _ = fs::remove_file(&path);
+        return path;
+    }
+
+    #[test]
+    fn least_recently_used_entries_get_evicted() {
+        let cache = Cache::default();
+        let options = TransformOptions::default();
+
+        for i in 0..CAPACITY {
+            cache.insert(&i.to_string(), &options, transformed(""));
+        }
+
+        // The first entry is used again, so the second one is the oldest.
+        assert!(cache.get("0", &options).is_some());
+        cache.insert("new", &options, transformed(""));
+
+        assert!(cache.get("0", &options).is_some());
+        assert!(cache.get("1", &options).is_none());
+        assert!(cache.get("2", &options).is_some());
+        assert!(cache.get("new", &options).is_some());
+    }
+
+    #[test]
+    fn entries_depend_on_the_options_and_the_globals() {
+        let cache = Cache::default();
+        let sprite = TransformOptions {
+            globals: Some(vec!["score".to_string()]),
+            ..Default::default()
+        };
+
+        cache.insert("self.move(1);", &sprite, transformed("sprite"));
+
+        let other = TransformOptions {
+            globals: Some(vec!["lives".to_string()]),
+            ..Default::default()
+        };
+        let debug = TransformOptions {
+            debug: true,
+            ..sprite.clone()
+        };
+
+        assert_eq!(cache.get("self.move(1);", &sprite).unwrap().code, "sprite");
+        assert!(cache.get("self.move(1);", &other).is_none());
+        assert!(cache.get("self.move(1);", &debug).is_none());
+        assert!(cache.get("self.move(2);", &sprite).is_none());
+    }
+
+    #[test]
+    fn persisted_entries_get_loaded() {
+        let path = file("persisted");
+        let options = TransformOptions::default();
+
+        let cache = Cache::new(Some(path.clone()));
+        cache.insert("self.move(1);", &options, transformed("moved"));
+        cache.persist();
+
+        let cache = Cache::new(Some(path.clone()));
+        assert_eq!(cache.get("self.move(1);", &options).unwrap().code, "moved");
+
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn corrupt_files_start_an_empty_cache() {
+        let path = file("corrupt");
+        let options = TransformOptions::default();
+        fs::write(&path, "{\"version\": ").unwrap();
+
+        let cache = Cache::new(Some(path.clone()));
+        assert!(cache.get("self.move(1);", &options).is_none());
+
+        // The corrupt file gets replaced.
+        cache.insert("self.move(1);", &options, transformed("moved"));
+        cache.persist();
+        assert!(Cache::new(Some(path.clone()))
+            .get("self.move(1);", &options)
+            .is_some());
+
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn files_of_other_versions_are_discarded() {
+        let path = file("version");
+        let options = TransformOptions::default();
+
+        let persisted = Persisted {
+            version: "0.0.0".to_string(),
+            entries: vec![Entry {
+                code: "self.move(1);".to_string(),
+                options: serde_json::to_string(&options).unwrap(),
+                transformed: transformed("old"),
+                used: 1,
+            }],
+        };
+        fs::write(&path, serde_json::to_vec(&persisted).unwrap()).unwrap();
+
+        let cache = Cache::new(Some(path.clone()));
+        assert!(cache.get("self.move(1);", &options).is_none());
+
+        let _ = fs::remove_file(&path);
+    }
+}
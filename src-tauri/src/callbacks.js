@@ -0,0 +1,90 @@
+// Awaited equivalents of the array methods, called in place
+// of them when their callback waits for the engine
+
+async function __forEach__(array, callback) {
+    for (let i = 0; i < array.length; i++) {
+        await callback(array[i], i, array);
+    }
+}
+
+async function __map__(array, callback) {
+    const result = [];
+
+    for (let i = 0; i < array.length; i++) {
+        result.push(await callback(array[i], i, array));
+    }
+
+    return result;
+}
+
+async function __filter__(array, callback) {
+    const result = [];
+
+    for (let i = 0; i < array.length; i++) {
+        if (await callback(array[i], i, array)) {
+            result.push(array[i]);
+        }
+    }
+
+    return result;
+}
+
+async function __some__(array, callback) {
+    for (let i = 0; i < array.length; i++) {
+        if (await callback(array[i], i, array)) {
+            return true;
+        }
+    }
+
+    return false;
+}
+
+async function __every__(array, callback) {
+    for (let i = 0; i < array.length; i++) {
+        if (!(await callback(array[i], i, array))) {
+            return false;
+        }
+    }
+
+    return true;
+}
+
+async function __find__(array, callback) {
+    for (let i = 0; i < array.length; i++) {
+        if (await callback(array[i], i, array)) {
+            return array[i];
+        }
+    }
+}
+
+async function __reduce__(array, callback, ...initial) {
+    if (!initial.length && !array.length) {
+        throw new TypeError("Reduce of empty array with no initial value");
+    }
+
+    let i = initial.length ? 0 : 1;
+    let accumulator = initial.length ? initial[0] : array[0];
+
+    for (; i < array.length; i++) {
+        accumulator = await callback(accumulator, array[i], i, array);
+    }
+
+    return accumulator;
+}
+
+// Insertion sort, stable like Array.prototype.sort
+async function __sort__(array, compare) {
+    for (let i = 1; i < array.length; i++) {
+        const item = array[i];
+        let j = i - 1;
+
+        while (j >= 0 && (await compare(array[j], item)) > 0) {
+            array[j + 1] = array[j];
+            j--;
+        }
+
+        array[j + 1] = item;
+    }
+
+    return array;
+}
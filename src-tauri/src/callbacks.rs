@@ -0,0 +1,65 @@
+use swc_core::{
+    atoms::Atom,
+    common::{sync::Lrc, FileName, SourceMap, Span, DUMMY_SP},
+    ecma::{
+        ast::*,
+        parser::{parse_file_as_script, Syntax},
+        visit::{VisitMut, VisitMutWith},
+    },
+};
+
+const HELPERS: &str = include_str!("callbacks.js");
+
+/// Array methods taking a callback, which has an awaited equivalent
+pub const METHODS: &[&str] = &[
+    "forEach", "map", "filter", "some", "every", "find", "reduce", "sort",
+];
+
+/// Name of the awaited equivalent of `method`
+pub fn helper(method: &str) -> String {
+    return format!("__{}__", method);
+}
+
+/// Declarations of the awaited equivalents of the `methods`
+pub fn helpers(methods: &[Atom]) -> Vec<Stmt> {
+    if methods.is_empty() {
+        return vec![];
+    }
+
+    let cm: Lrc<SourceMap> = Default::default();
+    let fm = cm.new_source_file(
+        FileName::Internal("callbacks.js".into()).into(),
+        HELPERS.into(),
+    );
+
+    let mut script = parse_file_as_script(
+        &fm,
+        Syntax::Es(Default::default()),
+        EsVersion::Es2015,
+        None,
+        &mut vec![],
+    )
+    .expect("Array method helpers don't parse");
+
+    // The spans point to a different source map.
+    script.visit_mut_with(&mut Unspan);
+
+    return script
+        .body
+        .into_iter()
+        .filter(|stmt| match stmt {
+            Stmt::Decl(Decl::Fn(decl)) => methods
+                .iter()
+                .any(|method| decl.ident.sym == helper(method)),
+            _ => false,
+        })
+        .collect();
+}
+
+struct Unspan;
+
+impl VisitMut for Unspan {
+    fn visit_mut_span(&mut self, span: &mut Span) {
+        *span = DUMMY_SP;
+    }
+}
@@ -0,0 +1,924 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::OnceLock,
+};
+
+use swc_core::{
+    atoms::Atom,
+    common::{
+        comments::SingleThreadedComments, sync::Lrc, FileName, FilePathMapping, Mark, SourceMap,
+        Span, Spanned, GLOBALS,
+    },
+    ecma::{
+        ast::*,
+        parser::{lexer::Lexer, Parser, StringInput, Syntax, TsSyntax},
+        transforms::base::resolver,
+        visit::{Visit, VisitMutWith, VisitWith},
+    },
+};
+
+use crate::diagnostics::Diagnostic;
+use crate::swc_utils::{get_property, parse_with, Parsed};
+use crate::types::Type;
+use crate::variables::{declarations, Declarations, VariableCheck};
+
+const STATIC: &str = include_str!("../../src/monaco-editor/lib/static.d.ts");
+
+// Interfaces whose members are fully declared in static.d.ts,
+// so accessing anything else on them is certainly an error.
+const SEALED: [&str; 4] = ["Stage", "Sprite", "Costumes", "Date"];
+
+static API: OnceLock<Api> = OnceLock::new();
+
+/// Declarations of the engine API, as written in static.d.ts
+pub struct Api {
+    interfaces: HashMap<String, Vec<TsInterfaceDecl>>,
+    aliases: HashMap<String, Box<TsType>>,
+    globals: HashMap<String, Box<TsType>>,
+}
+
+impl Api {
+    pub fn get() -> &'static Api {
+        return API.get_or_init(Api::load);
+    }
+
+    fn load() -> Api {
+        let cm = SourceMap::new(FilePathMapping::empty());
+        let fm = cm.new_source_file(
+            FileName::Custom("static.d.ts".into()).into(),
+            STATIC.to_string(),
+        );
+
+        let lexer = Lexer::new(
+            Syntax::Typescript(TsSyntax {
+                dts: true,
+                ..Default::default()
+            }),
+            EsVersion::Es2015,
+            StringInput::from(&*fm),
+            None,
+        );
+
+        let module = Parser::new_from(lexer)
+            .parse_module()
+            .expect("static.d.ts must be valid");
+
+        let mut api = Api {
+            interfaces: HashMap::new(),
+            aliases: HashMap::new(),
+            globals: HashMap::new(),
+        };
+
+        for item in module.body {
+            match item {
+                ModuleItem::Stmt(Stmt::Decl(Decl::TsInterface(interface))) => {
+                    api.interfaces
+                        .entry(interface.id.sym.to_string())
+                        .or_default()
+                        .push(*interface);
+                }
+                ModuleItem::Stmt(Stmt::Decl(Decl::TsTypeAlias(alias))) => {
+                    api.aliases.insert(alias.id.sym.to_string(), alias.type_ann);
+                }
+                ModuleItem::Stmt(Stmt::Decl(Decl::Var(var))) => {
+                    for decl in var.decls {
+                        if let Pat::Ident(BindingIdent {
+                            id,
+                            type_ann: Some(type_ann),
+                        }) = decl.name
+                        {
+                            api.globals.insert(id.sym.to_string(), type_ann.type_ann);
+                        }
+                    }
+                }
+                _ => continue,
+            }
+        }
+
+        return api;
+    }
+
+    fn interface(&'static self, name: &str) -> Option<&'static str> {
+        return self
+            .interfaces
+            .get_key_value(name)
+            .map(|(key, _)| key.as_str());
+    }
+
+    fn lookup(&'static self, interface: &str, name: &str) -> Option<Member<'static>> {
+        let decls = self.interfaces.get(interface)?;
+
+        for decl in decls.iter() {
+            if let Some(member) = find(&decl.body.body, name) {
+                return Some(member);
+            }
+        }
+
+        for decl in decls.iter() {
+            for parent in decl.extends.iter() {
+                if let Expr::Ident(id) = &*parent.expr {
+                    if let Some(member) = self.lookup(&id.sym, name) {
+                        return Some(member);
+                    }
+                }
+            }
+        }
+
+        return None;
+    }
+}
+
+#[derive(Clone)]
+pub enum Ty<'a> {
+    Any,
+    Void,
+    Null,
+    Undefined,
+    Number(Option<f64>),
+    String(Option<Atom>),
+    Boolean(Option<bool>),
+    Function,
+    Array,
+    Interface(&'static str),
+    Object(&'a str, &'a [TsTypeElement]),
+    Sprites,
+    Variables,
+}
+
+enum Member<'a> {
+    Property(&'a TsPropertySignature),
+    Methods(Vec<&'a TsMethodSignature>),
+    Variable(&'a TsType),
+}
+
+enum Lookup<'a> {
+    Unknown,
+    Missing(&'a str),
+    Found(Member<'a>),
+}
+
+fn key(expr: &Expr) -> Option<&str> {
+    match expr {
+        Expr::Ident(id) => Some(&id.sym),
+        Expr::Lit(Lit::Str(s)) => Some(&s.value),
+        _ => None,
+    }
+}
+
+fn find<'a>(elements: &'a [TsTypeElement], name: &str) -> Option<Member<'a>> {
+    let mut methods = vec![];
+
+    for element in elements.iter() {
+        match element {
+            TsTypeElement::TsPropertySignature(prop) if key(&prop.key) == Some(name) => {
+                return Some(Member::Property(prop));
+            }
+            TsTypeElement::TsMethodSignature(method) if key(&method.key) == Some(name) => {
+                methods.push(method);
+            }
+            _ => continue,
+        }
+    }
+
+    if methods.is_empty() {
+        return None;
+    }
+
+    return Some(Member::Methods(methods));
+}
+
+fn param(param: &TsFnParam) -> (Option<&TsType>, bool, bool) {
+    match param {
+        TsFnParam::Ident(binding) => (
+            binding.type_ann.as_ref().map(|t| &*t.type_ann),
+            binding.id.optional,
+            false,
+        ),
+        TsFnParam::Rest(_) => (None, true, true),
+        _ => (None, true, false),
+    }
+}
+
+fn arity(method: &TsMethodSignature) -> (usize, usize) {
+    let mut min = 0;
+    let mut max = 0;
+
+    for p in method.params.iter() {
+        let (_, optional, rest) = param(p);
+
+        if rest {
+            return (min, usize::MAX);
+        }
+
+        if !optional {
+            min += 1;
+        }
+
+        max += 1;
+    }
+
+    return (min, max);
+}
+
+/// Methods of arrays returning an array
+const ARRAY_METHODS: [&str; 6] = ["map", "filter", "sort", "slice", "reverse", "concat"];
+
+/// The type without references into the program, objects become any
+fn owned(ty: Ty) -> Ty<'static> {
+    return match ty {
+        Ty::Any | Ty::Object(..) => Ty::Any,
+        Ty::Void => Ty::Void,
+        Ty::Null => Ty::Null,
+        Ty::Undefined => Ty::Undefined,
+        Ty::Number(n) => Ty::Number(n),
+        Ty::String(s) => Ty::String(s),
+        Ty::Boolean(b) => Ty::Boolean(b),
+        Ty::Function => Ty::Function,
+        Ty::Array => Ty::Array,
+        Ty::Interface(name) => Ty::Interface(name),
+        Ty::Sprites => Ty::Sprites,
+        Ty::Variables => Ty::Variables,
+    };
+}
+
+pub fn describe(ty: &Ty) -> String {
+    match ty {
+        Ty::Any => "any",
+        Ty::Void => "void",
+        Ty::Null => "null",
+        Ty::Undefined => "undefined",
+        Ty::Number(_) => "number",
+        Ty::String(_) => "string",
+        Ty::Boolean(_) => "boolean",
+        Ty::Function => "function",
+        Ty::Array => "array",
+        Ty::Interface(name) => name,
+        Ty::Object(name, _) => name,
+        Ty::Sprites => "$",
+        Ty::Variables => "Variables",
+    }
+    .to_string()
+}
+
+/// Objects of member expressions whose type the checker knows, by the span
+/// of the member expression, so that the visitor only rewrites those
+#[derive(Default)]
+pub struct Receivers {
+    /// Sprites and the stage, e.g. `self` in `self.x`
+    pub entities: HashSet<Span>,
+    /// Arrays, e.g. `list` in `list.map`
+    pub arrays: HashSet<Span>,
+}
+
+pub struct Checker<'a> {
+    cm: &'a SourceMap,
+    api: &'static Api,
+    entity: &'static str,
+    variables: Declarations<'a>,
+    /// Types of the local bindings, declared or of their initializer
+    locals: HashMap<Id, Ty<'static>>,
+    pub receivers: Receivers,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> Checker<'a> {
+    pub fn new(cm: &'a SourceMap, program: &'a Program, stage: bool) -> Checker<'a> {
+        return Checker {
+            cm,
+            api: Api::get(),
+            entity: if stage { "Stage" } else { "Sprite" },
+            variables: declarations(program, None),
+            locals: HashMap::new(),
+            receivers: Receivers::default(),
+            diagnostics: vec![],
+        };
+    }
+
+    fn error(&mut self, span: swc_core::common::Span, message: String) {
+        self.diagnostics
+            .push(Diagnostic::error(self.cm, span, message));
+    }
+
+    fn resolve<'b>(&self, t: &'b TsType, name: &'b str) -> Ty<'b> {
+        match t {
+            TsType::TsKeywordType(keyword) => match keyword.kind {
+                TsKeywordTypeKind::TsNumberKeyword => Ty::Number(None),
+                TsKeywordTypeKind::TsStringKeyword => Ty::String(None),
+                TsKeywordTypeKind::TsBooleanKeyword => Ty::Boolean(None),
+                TsKeywordTypeKind::TsVoidKeyword => Ty::Void,
+                TsKeywordTypeKind::TsNullKeyword => Ty::Null,
+                TsKeywordTypeKind::TsUndefinedKeyword => Ty::Undefined,
+                _ => Ty::Any,
+            },
+            TsType::TsThisType(_) => Ty::Interface(self.entity),
+            TsType::TsArrayType(_) => Ty::Array,
+            TsType::TsParenthesizedType(paren) => self.resolve(&paren.type_ann, name),
+            TsType::TsTypeLit(lit) => Ty::Object(name, &lit.members),
+            TsType::TsFnOrConstructorType(_) => Ty::Function,
+            TsType::TsLitType(lit) => match &lit.lit {
+                TsLit::Number(n) => Ty::Number(Some(n.value)),
+                TsLit::Str(s) => Ty::String(Some(s.value.clone())),
+                TsLit::Bool(b) => Ty::Boolean(Some(b.value)),
+                TsLit::Tpl(_) => Ty::String(None),
+                _ => Ty::Any,
+            },
+            TsType::TsTypeRef(TsTypeRef {
+                type_name: TsEntityName::Ident(id),
+                ..
+            }) => {
+                if id.sym == "Array" {
+                    Ty::Array
+                } else if let Some(alias) = self.api.aliases.get(&*id.sym) {
+                    self.resolve(alias, name)
+                } else if let Some(interface) = self.api.interface(&id.sym) {
+                    Ty::Interface(interface)
+                } else {
+                    Ty::Any
+                }
+            }
+            TsType::TsUnionOrIntersectionType(TsUnionOrIntersectionType::TsUnionType(union)) => {
+                let types: Vec<Ty> = union.types.iter().map(|t| self.resolve(t, name)).collect();
+
+                if types.iter().all(|t| matches!(t, Ty::Number(_))) {
+                    Ty::Number(None)
+                } else if types.iter().all(|t| matches!(t, Ty::String(_))) {
+                    Ty::String(None)
+                } else if types.iter().all(|t| matches!(t, Ty::Boolean(_))) {
+                    Ty::Boolean(None)
+                } else {
+                    Ty::Any
+                }
+            }
+            _ => Ty::Any,
+        }
+    }
+
+    fn lookup(&self, ty: &Ty<'a>, name: &str) -> Lookup<'a> {
+        match ty {
+            Ty::Interface(interface) => match self.api.lookup(interface, name) {
+                Some(member) => Lookup::Found(member),
+                None if SEALED.contains(interface) => Lookup::Missing(interface),
+                None => Lookup::Unknown,
+            },
+            Ty::Object(object, elements) => match find(elements, name) {
+                Some(member) => Lookup::Found(member),
+                None => Lookup::Missing(object),
+            },
+            Ty::Variables => match self.variables.type_of(name) {
+                Some(t) => Lookup::Found(Member::Variable(t)),
+                None => Lookup::Unknown,
+            },
+            _ => Lookup::Unknown,
+        }
+    }
+
+    pub fn infer(&self, expr: &Expr) -> Ty<'a> {
+        match expr {
+            Expr::Lit(Lit::Str(s)) => Ty::String(Some(s.value.clone())),
+            Expr::Lit(Lit::Num(n)) => Ty::Number(Some(n.value)),
+            Expr::Lit(Lit::Bool(b)) => Ty::Boolean(Some(b.value)),
+            Expr::Lit(Lit::Null(_)) => Ty::Null,
+            Expr::Tpl(_) => Ty::String(None),
+            Expr::Arrow(_) | Expr::Fn(_) => Ty::Function,
+            Expr::Array(_) => Ty::Array,
+            Expr::Paren(paren) => self.infer(&paren.expr),
+            Expr::Seq(seq) => match seq.exprs.last() {
+                Some(last) => self.infer(last),
+                None => Ty::Any,
+            },
+            Expr::Assign(assign) => self.infer(&assign.right),
+            Expr::Ident(id) => {
+                if id.sym == "self" {
+                    Ty::Interface(self.entity)
+                } else if id.sym == "$" {
+                    Ty::Sprites
+                } else if id.sym == "undefined" {
+                    Ty::Undefined
+                } else if let Some(ty) = self.locals.get(&id.to_id()) {
+                    ty.clone()
+                } else if let Some((name, t)) = self.api.globals.get_key_value(&*id.sym) {
+                    self.resolve(t, name)
+                } else {
+                    Ty::Any
+                }
+            }
+            Expr::Unary(unary) => match unary.op {
+                UnaryOp::Minus => match self.infer(&unary.arg) {
+                    Ty::Number(Some(n)) => Ty::Number(Some(-n)),
+                    _ => Ty::Number(None),
+                },
+                UnaryOp::Plus | UnaryOp::Tilde => Ty::Number(None),
+                UnaryOp::Bang | UnaryOp::Delete => Ty::Boolean(None),
+                UnaryOp::TypeOf => Ty::String(None),
+                UnaryOp::Void => Ty::Undefined,
+            },
+            Expr::Bin(bin) => match bin.op {
+                BinaryOp::Add => match (self.infer(&bin.left), self.infer(&bin.right)) {
+                    (Ty::String(_), _) | (_, Ty::String(_)) => Ty::String(None),
+                    (Ty::Number(_), Ty::Number(_)) => Ty::Number(None),
+                    _ => Ty::Any,
+                },
+                BinaryOp::Sub
+                | BinaryOp::Mul
+                | BinaryOp::Div
+                | BinaryOp::Mod
+                | BinaryOp::Exp
+                | BinaryOp::BitAnd
+                | BinaryOp::BitOr
+                | BinaryOp::BitXor
+                | BinaryOp::LShift
+                | BinaryOp::RShift
+                | BinaryOp::ZeroFillRShift => Ty::Number(None),
+                BinaryOp::LogicalAnd | BinaryOp::LogicalOr | BinaryOp::NullishCoalescing => Ty::Any,
+                _ => Ty::Boolean(None),
+            },
+            Expr::New(new) => match &*new.callee {
+                Expr::Ident(id) if id.sym == "Array" => Ty::Array,
+                Expr::Ident(id) if id.sym == "Date" => Ty::Interface("Date"),
+                _ => Ty::Any,
+            },
+            Expr::Call(call) => match &call.callee {
+                Callee::Expr(callee) => match &**callee {
+                    Expr::Ident(id) if id.sym == "String" => Ty::String(None),
+                    Expr::Ident(id) if id.sym == "Number" => Ty::Number(None),
+                    Expr::Ident(id) if id.sym == "Boolean" => Ty::Boolean(None),
+                    Expr::Member(member) => {
+                        let name = match get_property(member) {
+                            Some(name) => name,
+                            None => return Ty::Any,
+                        };
+
+                        let object = self.infer(&member.obj);
+
+                        if let Ty::Array = object {
+                            if ARRAY_METHODS.contains(&name.as_str()) {
+                                return Ty::Array;
+                            }
+                        }
+
+                        match self.lookup(&object, &name) {
+                            Lookup::Found(Member::Methods(methods)) => match &methods[0].type_ann {
+                                Some(t) => self.resolve(&t.type_ann, "result"),
+                                None => Ty::Any,
+                            },
+                            _ => Ty::Any,
+                        }
+                    }
+                    _ => Ty::Any,
+                },
+                _ => Ty::Any,
+            },
+            Expr::Member(member) => {
+                let object = self.infer(&member.obj);
+
+                if let Ty::Sprites = object {
+                    if get_property(member).is_some_and(|name| name == "Stage") {
+                        return Ty::Interface("Stage");
+                    }
+
+                    return Ty::Interface("Sprite");
+                }
+
+                if let (Ty::Interface(_), MemberProp::Ident(id)) = (&object, &member.prop) {
+                    if id.sym == "variables" {
+                        return Ty::Variables;
+                    }
+                }
+
+                let name = match get_property(member) {
+                    Some(name) => name,
+                    None => return Ty::Any,
+                };
+
+                match self.lookup(&object, &name) {
+                    Lookup::Found(Member::Property(prop)) => match (&prop.type_ann, key(&prop.key))
+                    {
+                        (Some(t), Some(name)) => self.resolve(&t.type_ann, name),
+                        _ => Ty::Any,
+                    },
+                    Lookup::Found(Member::Methods(_)) => Ty::Function,
+                    Lookup::Found(Member::Variable(t)) => self.resolve(t, "variable"),
+                    _ => Ty::Any,
+                }
+            }
+            _ => Ty::Any,
+        }
+    }
+
+    pub fn assignable(&self, source: &Ty, target: &TsType) -> bool {
+        if let Ty::Any | Ty::Null | Ty::Undefined = source {
+            return true;
+        }
+
+        match target {
+            TsType::TsKeywordType(keyword) => match keyword.kind {
+                TsKeywordTypeKind::TsNumberKeyword => matches!(source, Ty::Number(_)),
+                TsKeywordTypeKind::TsStringKeyword => matches!(source, Ty::String(_)),
+                TsKeywordTypeKind::TsBooleanKeyword => matches!(source, Ty::Boolean(_)),
+                TsKeywordTypeKind::TsVoidKeyword => matches!(source, Ty::Void),
+                TsKeywordTypeKind::TsNeverKeyword => false,
+                _ => true,
+            },
+            TsType::TsLitType(lit) => match (&lit.lit, source) {
+                (TsLit::Str(expected), Ty::String(Some(actual))) => expected.value == *actual,
+                (TsLit::Number(expected), Ty::Number(Some(actual))) => expected.value == *actual,
+                (TsLit::Bool(expected), Ty::Boolean(Some(actual))) => expected.value == *actual,
+                (TsLit::Tpl(_), Ty::String(_)) => true,
+                _ => false,
+            },
+            TsType::TsParenthesizedType(paren) => self.assignable(source, &paren.type_ann),
+            TsType::TsUnionOrIntersectionType(TsUnionOrIntersectionType::TsUnionType(union)) => {
+                union.types.iter().any(|t| self.assignable(source, t))
+            }
+            TsType::TsUnionOrIntersectionType(TsUnionOrIntersectionType::TsIntersectionType(
+                intersection,
+            )) => intersection
+                .types
+                .iter()
+                .all(|t| self.assignable(source, t)),
+            TsType::TsArrayType(_) => matches!(source, Ty::Array),
+            TsType::TsFnOrConstructorType(_) => matches!(source, Ty::Function),
+            TsType::TsTypeOperator(_) => matches!(source, Ty::String(_)),
+            TsType::TsTypeRef(TsTypeRef {
+                type_name: TsEntityName::Ident(id),
+                ..
+            }) => {
+                if id.sym == "Array" {
+                    matches!(source, Ty::Array)
+                } else if let Some(alias) = self.api.aliases.get(&*id.sym) {
+                    self.assignable(source, alias)
+                } else if SEALED.contains(&&*id.sym) {
+                    match source {
+                        Ty::Interface(name) => *name == &*id.sym || *name == "Sprite",
+                        _ => false,
+                    }
+                } else {
+                    true
+                }
+            }
+            _ => true,
+        }
+    }
+
+    fn check_arguments(
+        &mut self,
+        call: &CallExpr,
+        name: &str,
+        methods: Vec<&'a TsMethodSignature>,
+    ) {
+        if call.args.iter().any(|arg| arg.spread.is_some()) {
+            return;
+        }
+
+        let args: Vec<Ty> = call.args.iter().map(|arg| self.infer(&arg.expr)).collect();
+
+        let candidates: Vec<&TsMethodSignature> = methods
+            .iter()
+            .copied()
+            .filter(|method| {
+                let (min, max) = arity(method);
+                min <= args.len() && args.len() <= max
+            })
+            .collect();
+
+        if candidates.is_empty() {
+            let min = methods.iter().map(|m| arity(m).0).min().unwrap_or(0);
+            let max = methods.iter().map(|m| arity(m).1).max().unwrap_or(0);
+
+            let expected = if min == max {
+                min.to_string()
+            } else if max == usize::MAX {
+                format!("at least {}", min)
+            } else {
+                format!("{}-{}", min, max)
+            };
+
+            self.error(
+                call.span,
+                format!("Expected {} arguments, but got {}.", expected, args.len()),
+            );
+            return;
+        }
+
+        let mismatch = |method: &TsMethodSignature| {
+            args.iter()
+                .zip(method.params.iter())
+                .position(|(arg, p)| match param(p).0 {
+                    Some(t) => !self.assignable(arg, t),
+                    None => false,
+                })
+        };
+
+        if candidates.iter().any(|method| mismatch(method).is_none()) {
+            return;
+        }
+
+        if candidates.len() == 1 {
+            let method = candidates[0];
+
+            if let Some(i) = mismatch(method) {
+                let target = param(&method.params[i])
+                    .0
+                    .map(Type::from_ts)
+                    .unwrap_or(Type::Any);
+                let message = format!(
+                    "Argument of type '{}' is not assignable to parameter of type '{}'.",
+                    describe(&args[i]),
+                    target
+                );
+
+                self.error(call.args[i].expr.span(), message);
+            }
+        } else {
+            self.error(
+                call.span,
+                format!("No overload of '{}' matches this call.", name),
+            );
+        }
+    }
+
+    fn check_write(&mut self, target: &MemberExpr, value: Option<&Expr>) {
+        let name = match get_property(target) {
+            Some(name) => name,
+            None => return,
+        };
+
+        if let Lookup::Found(Member::Property(prop)) = self.lookup(&self.infer(&target.obj), &name)
+        {
+            if prop.readonly {
+                self.error(
+                    target.span,
+                    format!(
+                        "Cannot assign to '{}' because it is a read-only property.",
+                        name
+                    ),
+                );
+            } else if let (Some(t), Some(value)) = (&prop.type_ann, value) {
+                let source = self.infer(value);
+
+                if !self.assignable(&source, &t.type_ann) {
+                    self.error(
+                        value.span(),
+                        format!(
+                            "Type '{}' is not assignable to type '{}'.",
+                            describe(&source),
+                            Type::from_ts(&t.type_ann)
+                        ),
+                    );
+                }
+            }
+        }
+    }
+}
+
+impl Visit for Checker<'_> {
+    fn visit_binding_ident(&mut self, node: &BindingIdent) {
+        if let Some(t) = &node.type_ann {
+            let ty = owned(self.resolve(&t.type_ann, &node.id.sym));
+            self.locals.insert(node.id.to_id(), ty);
+        }
+    }
+
+    fn visit_var_declarator(&mut self, node: &VarDeclarator) {
+        node.visit_children_with(self);
+
+        if let (Pat::Ident(binding), Some(init)) = (&node.name, &node.init) {
+            if binding.type_ann.is_none() {
+                let ty = owned(self.infer(init));
+                self.locals.insert(binding.id.to_id(), ty);
+            }
+        }
+    }
+
+    fn visit_member_expr(&mut self, node: &MemberExpr) {
+        node.visit_children_with(self);
+
+        let object = self.infer(&node.obj);
+
+        match object {
+            Ty::Interface("Sprite" | "Stage") => {
+                self.receivers.entities.insert(node.span);
+            }
+            Ty::Array => {
+                self.receivers.arrays.insert(node.span);
+            }
+            _ => {}
+        }
+
+        let name = match get_property(node) {
+            Some(name) => name,
+            None => return,
+        };
+
+        if let Lookup::Missing(owner) = self.lookup(&object, &name) {
+            self.error(
+                node.prop.span(),
+                format!("Property '{}' does not exist on type '{}'.", name, owner),
+            );
+        }
+    }
+
+    fn visit_call_expr(&mut self, node: &CallExpr) {
+        node.visit_children_with(self);
+
+        if let Callee::Expr(callee) = &node.callee {
+            if let Expr::Member(member) = &**callee {
+                let name = match get_property(member) {
+                    Some(name) => name,
+                    None => return,
+                };
+
+                if let Lookup::Found(Member::Methods(methods)) =
+                    self.lookup(&self.infer(&member.obj), &name)
+                {
+                    self.check_arguments(node, &name, methods);
+                }
+            }
+        }
+    }
+
+    fn visit_assign_expr(&mut self, node: &AssignExpr) {
+        node.visit_children_with(self);
+
+        if let AssignTarget::Simple(SimpleAssignTarget::Member(member)) = &node.left {
+            match node.op {
+                AssignOp::Assign => self.check_write(member, Some(&node.right)),
+                _ => self.check_write(member, None),
+            }
+        }
+    }
+
+    fn visit_update_expr(&mut self, node: &UpdateExpr) {
+        node.visit_children_with(self);
+
+        if let Expr::Member(member) = &*node.arg {
+            self.check_write(member, None);
+        }
+    }
+}
+
+pub fn check(code: String, stage: bool, globals: Option<Vec<String>>) -> Vec<Diagnostic> {
+    let cm = Lrc::new(SourceMap::new(FilePathMapping::empty()));
+    let comments = SingleThreadedComments::default();
+
+    let Parsed {
+        module,
+        mut diagnostics,
+    } = match parse_with(&cm, code, Some(&comments)) {
+        Ok(parsed) => parsed,
+        Err(diagnostics) => return diagnostics,
+    };
+
+    let mut program = Program::Module(module);
+
+    return GLOBALS.set(&Default::default(), || {
+        // Bindings get told apart by their syntax context.
+        program.visit_mut_with(&mut resolver(Mark::new(), Mark::new(), true));
+
+        let mut checker = Checker::new(&cm, &program, stage);
+        program.visit_with(&mut checker);
+        diagnostics.append(&mut checker.diagnostics);
+
+        let mut variables =
+            VariableCheck::new(&cm, &checker, &program, Some(&comments), stage, globals);
+        program.visit_with(&mut variables);
+        diagnostics.extend(variables.finish());
+
+        return diagnostics;
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::check;
+    use crate::diagnostics::Severity;
+
+    const SPRITE: &str = r#"interface Variables {
+	score: number;
+	names: string[];
+}
+
+function patrol(steps: number): number {
+	let travelled = 0;
+	for (let step = 0; step < steps; step++) {
+		self.move(10);
+		self.x += 2;
+		self.variables.score++;
+		if (self.isTouchingEdge()) {
+			self.turnRight(180);
+		}
+		travelled += 10;
+	}
+	return travelled;
+}
+
+self.whenFlag(() => {
+	self.goTo(0, 0);
+	self.show();
+	self.variables.score = 0;
+	self.variables.names.push("Scrappy");
+	const longest = self.variables.names.filter(name => name.length > 3).length;
+	self.sayWait(`Longest ${longest}`, 2);
+	const answer = self.ask("Name?");
+	self.say(answer.slice(0, 3));
+	if (self.isTouching($["Enemy"]) || self.distanceTo(0, 0) < 10) {
+		self.playSound("pop");
+	}
+	self.penDown();
+	self.pointInDirection(45);
+	self.switchCostumeTo(1);
+	const names = self.variables.names.join(", ");
+	const today = new Date().getDay();
+	self.think(names + today + Math.floor(self.getTimer()));
+	while (self.variables.score < 100) {
+		self.variables.score += patrol(3) > 5 ? 1 : 2;
+		self.wait(0.1);
+	}
+	self.effects.color = Math.round(Math.random() * 100);
+	self.size = 120;
+	self.direction = 90;
+});
+
+self.whenKeyPressed("space", () => {
+	self.y += 20;
+	self.glide(1, self.x, 0);
+});
+
+self.whenReceiveMessage("level", () => {
+	self.hide();
+});
+"#;
+
+    const STAGE: &str = r#"interface Variables {
+	level: number;
+}
+
+self.whenFlag(() => {
+	self.variables.level = 0;
+	for (let level = 0; level < 5; level++) {
+		self.broadcastMessageWait(`level ${level}`);
+		self.variables.level = level;
+	}
+	self.wait(1);
+});
+"#;
+
+    fn errors(code: &str, stage: bool) -> Vec<String> {
+        return check(code.to_string(), stage, None)
+            .into_iter()
+            .filter(|diagnostic| diagnostic.severity == Severity::Error)
+            .map(|diagnostic| diagnostic.message)
+            .collect();
+    }
+
+    #[test]
+    fn sprites_using_the_engine_api_have_no_errors() {
+        assert_eq!(errors(SPRITE, false), Vec::<String>::new());
+    }
+
+    #[test]
+    fn the_stage_using_the_engine_api_has_no_errors() {
+        assert_eq!(errors(STAGE, true), Vec::<String>::new());
+    }
+
+    #[test]
+    fn misuses_of_the_engine_api_are_errors() {
+        let errors = errors("self.say(\"hi\", 2);\nself.changeY(20);\n", false);
+
+        assert_eq!(
+            errors,
+            [
+                "Expected 1 arguments, but got 2.",
+                "Property 'changeY' does not exist on type 'Sprite'.",
+            ]
+        );
+    }
+
+    #[test]
+    fn sprites_can_use_the_variables_of_the_stage() {
+        let code = "self.whenFlag(() => {\n\tself.say(self.variables.level);\n});\n";
+        let diagnostics = check(code.to_string(), false, Some(vec!["level".to_string()]));
+
+        assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+    }
+
+    #[test]
+    fn errors_point_at_the_wrong_code() {
+        let diagnostics = check(
+            "self.move(10);\nself.wait(\"two\");\n".to_string(),
+            false,
+            None,
+        );
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].message,
+            "Argument of type 'string' is not assignable to parameter of type 'number'."
+        );
+        assert_eq!(diagnostics[0].span, (25, 30));
+        assert_eq!(
+            (diagnostics[0].start.line, diagnostics[0].start.column),
+            (2, 11)
+        );
+    }
+}
@@ -0,0 +1,96 @@
+use std::sync::Mutex;
+
+use swc::Compiler;
+use swc_core::common::{sync::Lrc, FilePathMapping, SourceMap};
+
+/// Uses before a compiler gets dropped, as its source map
+/// keeps every file it has compiled
+const USES: usize = 16;
+
+struct Pooled {
+    compiler: Compiler,
+    uses: usize,
+}
+
+/// Compilers reused between the invocations,
+/// as many as there have been compiling at once
+#[derive(Default)]
+pub struct Compilers {
+    idle: Mutex<Vec<Pooled>>,
+}
+
+impl Compilers {
+    /// Runs `f` with an idle compiler, or a new one if there is none
+    pub fn with<T>(&self, f: impl FnOnce(&Compiler) -> T) -> T {
+        let idle = self.idle.lock().unwrap().pop();
+
+        let mut pooled = idle.unwrap_or_else(|| Pooled {
+            compiler: Compiler::new(Lrc::new(SourceMap::new(FilePathMapping::empty()))),
+            uses: 0,
+        });
+
+        let result = f(&pooled.compiler);
+        pooled.uses += 1;
+
+        // Comments are keyed by position, none of them is needed again.
+        let comments = pooled.compiler.comments();
+        comments.leading.clear();
+        comments.trailing.clear();
+
+        if pooled.uses < USES {
+            self.idle.lock().unwrap().push(pooled);
+        }
+
+        return result;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Compilers;
+    use crate::swc_utils::{parse, transform};
+
+    const CODE: &str = "// Moves\nself.whenFlag(() => {\n\tself.move(\"far\");\n});\n";
+
+    #[test]
+    fn pooled_compilers_report_spans_relative_to_the_file() {
+        let compilers = Compilers::default();
+
+        let spans: Vec<Vec<(u32, u32)>> = (0..3)
+            .map(|_| {
+                let transformed = compilers
+                    .with(|compiler| transform(compiler, CODE.to_string(), Default::default()))
+                    .unwrap();
+
+                return transformed
+                    .diagnostics
+                    .iter()
+                    .map(|diagnostic| diagnostic.span)
+                    .collect();
+            })
+            .collect();
+
+        assert!(!spans[0].is_empty());
+        assert_eq!(spans[0], spans[1]);
+        assert_eq!(spans[0], spans[2]);
+    }
+
+    #[test]
+    fn pooled_compilers_forget_the_comments() {
+        let compilers = Compilers::default();
+        compilers
+            .with(|compiler| transform(compiler, CODE.to_string(), Default::default()))
+            .unwrap();
+
+        let comments = compilers.with(|compiler| compiler.comments().leading.len());
+        assert_eq!(comments, 0);
+    }
+
+    #[test]
+    fn parsed_spans_are_relative_to_the_file() {
+        let first = parse(CODE.to_string()).unwrap();
+        let second = parse(CODE.to_string()).unwrap();
+
+        assert_eq!(first.module.span, second.module.span);
+    }
+}
@@ -0,0 +1,167 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use swc_core::common::{SourceMap, Span};
+
+use crate::diagnostics::{offset, position, Position};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Kind {
+    Statement,
+    /// Arm of an `if`, `switch`, `?:`, `&&`, `||` or `??`
+    Branch,
+}
+
+/// Statement or branch counted with `self.__cover(id)`,
+/// where `id` is its index in the table
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Counter {
+    pub span: (u32, u32),
+    pub start: Position,
+    pub end: Position,
+    pub kind: Kind,
+    /// ID of the block the statement was generated from
+    pub block: Option<String>,
+}
+
+impl Counter {
+    pub fn new(cm: &SourceMap, span: Span, kind: Kind, block: Option<String>) -> Counter {
+        return Counter {
+            span: (offset(cm, span.lo), offset(cm, span.hi)),
+            start: position(cm, span.lo),
+            end: position(cm, span.hi),
+            kind,
+            block,
+        };
+    }
+}
+
+/// An entity after a preview session
+#[derive(Deserialize, Debug)]
+pub struct Session {
+    /// Code that was transformed
+    pub code: String,
+    pub counters: Vec<Counter>,
+    /// Executions, indexed by counter ID
+    pub hits: Vec<u64>,
+}
+
+#[derive(Serialize, Default, Clone, Debug)]
+pub struct Line {
+    /// Executions of the statements starting on the line,
+    /// `None` if there are none
+    pub hits: Option<u64>,
+    /// Branches starting on the line
+    pub branches: u32,
+    /// Branches that were taken at least once
+    pub taken: u32,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Coverage {
+    /// Indexed by line number minus one
+    pub lines: Vec<Line>,
+    /// Executions of the generated blocks, by block ID
+    pub blocks: HashMap<String, u64>,
+}
+
+pub fn report(session: Session) -> Coverage {
+    let length = session
+        .counters
+        .iter()
+        .map(|counter| counter.start.line)
+        .max()
+        .unwrap_or(0)
+        .max(session.code.lines().count());
+
+    let mut lines = vec![Line::default(); length];
+    let mut blocks: HashMap<String, u64> = HashMap::new();
+
+    for (id, counter) in session.counters.into_iter().enumerate() {
+        let hits = session.hits.get(id).copied().unwrap_or(0);
+        let line = match lines.get_mut(counter.start.line.wrapping_sub(1)) {
+            Some(line) => line,
+            None => continue,
+        };
+
+        match counter.kind {
+            Kind::Statement => line.hits = Some(line.hits.unwrap_or(0).max(hits)),
+            Kind::Branch => {
+                line.branches += 1;
+
+                if hits > 0 {
+                    line.taken += 1;
+                }
+            }
+        }
+
+        if let Some(block) = counter.block {
+            let total = blocks.entry(block).or_default();
+            *total = (*total).max(hits);
+        }
+    }
+
+    return Coverage { lines, blocks };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn counter(line: usize, kind: Kind, block: Option<&str>) -> Counter {
+        return Counter {
+            span: (0, 0),
+            start: Position { line, column: 0 },
+            end: Position { line, column: 1 },
+            kind,
+            block: block.map(str::to_string),
+        };
+    }
+
+    #[test]
+    fn lines_count_their_statements_and_branches() {
+        let coverage = report(Session {
+            code: "a\nb\nc\nd".to_string(),
+            counters: vec![
+                counter(1, Kind::Statement, None),
+                counter(1, Kind::Statement, None),
+                counter(2, Kind::Branch, None),
+                counter(2, Kind::Branch, None),
+                counter(2, Kind::Branch, None),
+                counter(3, Kind::Statement, None),
+            ],
+            hits: vec![3, 1, 0, 2, 5],
+        });
+
+        let lines: Vec<(Option<u64>, u32, u32)> = coverage
+            .lines
+            .iter()
+            .map(|line| (line.hits, line.branches, line.taken))
+            .collect();
+
+        // Counters without hits never ran.
+        assert_eq!(
+            lines,
+            vec![(Some(3), 0, 0), (None, 3, 2), (Some(0), 0, 0), (None, 0, 0)]
+        );
+    }
+
+    #[test]
+    fn blocks_count_their_most_executed_statement() {
+        let coverage = report(Session {
+            code: "a".to_string(),
+            counters: vec![
+                counter(1, Kind::Statement, Some("b1")),
+                counter(3, Kind::Branch, Some("b1")),
+                counter(2, Kind::Statement, Some("b2")),
+            ],
+            hits: vec![1, 4, 0],
+        });
+
+        // Counters past the end of the code add lines.
+        assert_eq!(coverage.lines.len(), 3);
+        assert_eq!(coverage.blocks.get("b1"), Some(&4));
+        assert_eq!(coverage.blocks.get("b2"), Some(&0));
+    }
+}
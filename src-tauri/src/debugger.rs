@@ -0,0 +1,27 @@
+use serde::{Deserialize, Serialize};
+use swc_core::common::{SourceMap, Span};
+
+use crate::diagnostics::{offset, position, Position};
+
+/// Statement instrumented with `self.__probe(id, ...)`,
+/// where `id` is its index in the table
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Probe {
+    pub span: (u32, u32),
+    pub start: Position,
+    pub end: Position,
+    /// Enclosing function, `None` for the main script
+    /// or an anonymous function
+    pub function: Option<String>,
+}
+
+impl Probe {
+    pub fn new(cm: &SourceMap, span: Span, function: Option<String>) -> Probe {
+        return Probe {
+            span: (offset(cm, span.lo), offset(cm, span.hi)),
+            start: position(cm, span.lo),
+            end: position(cm, span.hi),
+            function,
+        };
+    }
+}
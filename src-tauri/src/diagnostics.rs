@@ -0,0 +1,154 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    any::Any,
+    sync::{Arc, Mutex},
+};
+use swc_core::common::{
+    errors::{DiagnosticBuilder, Emitter, Handler, Level},
+    sync::Lrc,
+    BytePos, SourceMap, Span, DUMMY_SP,
+};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+    Info,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Diagnostic {
+    pub message: String,
+    pub severity: Severity,
+    pub span: (u32, u32),
+    pub start: Position,
+    pub end: Position,
+}
+
+impl Diagnostic {
+    pub fn new(cm: &SourceMap, span: Span, message: String, severity: Severity) -> Diagnostic {
+        return Diagnostic {
+            message,
+            severity,
+            span: (offset(cm, span.lo), offset(cm, span.hi)),
+            start: position(cm, span.lo),
+            end: position(cm, span.hi),
+        };
+    }
+
+    pub fn error(cm: &SourceMap, span: Span, message: String) -> Diagnostic {
+        return Diagnostic::new(cm, span, message, Severity::Error);
+    }
+
+    pub fn warning(cm: &SourceMap, span: Span, message: String) -> Diagnostic {
+        return Diagnostic::new(cm, span, message, Severity::Warning);
+    }
+}
+
+#[derive(Serialize, Debug)]
+#[serde(tag = "kind", content = "diagnostics", rename_all = "camelCase")]
+pub enum CompileError {
+    Parse(Vec<Diagnostic>),
+    Type(Vec<Diagnostic>),
+    Codegen(Vec<Diagnostic>),
+    Unsupported(Vec<Diagnostic>),
+    /// The compiler itself failed, e.g. it panicked
+    Internal(Vec<Diagnostic>),
+}
+
+impl CompileError {
+    pub fn internal(message: String) -> CompileError {
+        let cm = SourceMap::default();
+        return CompileError::Internal(vec![Diagnostic::error(&cm, DUMMY_SP, message)]);
+    }
+
+    /// Keeps the message of a panic caught while compiling
+    pub fn panicked(panic: Box<dyn Any + Send>) -> CompileError {
+        let message = match panic.downcast::<String>() {
+            Ok(message) => *message,
+            Err(panic) => match panic.downcast::<&str>() {
+                Ok(message) => message.to_string(),
+                Err(_) => "unknown error".to_string(),
+            },
+        };
+
+        return CompileError::internal(format!("The compiler failed: {}", message));
+    }
+}
+
+impl From<CompileError> for Vec<Diagnostic> {
+    fn from(error: CompileError) -> Vec<Diagnostic> {
+        return match error {
+            CompileError::Parse(diagnostics)
+            | CompileError::Type(diagnostics)
+            | CompileError::Codegen(diagnostics)
+            | CompileError::Unsupported(diagnostics)
+            | CompileError::Internal(diagnostics) => diagnostics,
+        };
+    }
+}
+
+/// Collects everything emitted to a [`Handler`]
+/// instead of printing it to stderr.
+#[derive(Clone)]
+pub struct Collector {
+    cm: Lrc<SourceMap>,
+    diagnostics: Arc<Mutex<Vec<Diagnostic>>>,
+}
+
+impl Collector {
+    pub fn new(cm: Lrc<SourceMap>) -> Collector {
+        return Collector {
+            cm,
+            diagnostics: Default::default(),
+        };
+    }
+
+    pub fn handler(&self) -> Handler {
+        return Handler::with_emitter(true, false, Box::new(self.clone()));
+    }
+
+    pub fn take(&self) -> Vec<Diagnostic> {
+        return std::mem::take(&mut *self.diagnostics.lock().unwrap());
+    }
+}
+
+impl Emitter for Collector {
+    fn emit(&mut self, db: &DiagnosticBuilder<'_>) {
+        let severity = match db.level {
+            Level::Warning => Severity::Warning,
+            Level::Note | Level::Help => Severity::Info,
+            _ => Severity::Error,
+        };
+
+        let span = db.span.primary_span().unwrap_or(DUMMY_SP);
+        let diagnostic = Diagnostic::new(&self.cm, span, db.message(), severity);
+
+        self.diagnostics.lock().unwrap().push(diagnostic);
+    }
+}
+
+pub fn offset(cm: &SourceMap, pos: BytePos) -> u32 {
+    match cm.try_lookup_byte_offset(pos) {
+        Ok(file) => file.pos.0,
+        Err(_) => 0,
+    }
+}
+
+// Lines are 1-based as in Monaco, so are the columns.
+pub fn position(cm: &SourceMap, pos: BytePos) -> Position {
+    match cm.try_lookup_char_pos(pos) {
+        Ok(loc) => Position {
+            line: loc.line,
+            column: loc.col.0 + 1,
+        },
+        Err(_) => Position { line: 1, column: 1 },
+    }
+}
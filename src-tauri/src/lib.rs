@@ -1,27 +1,141 @@
-mod swc_utils;
+pub mod cache;
+mod callbacks;
+mod checker;
+pub mod compilers;
+mod coverage;
+mod debugger;
+mod diagnostics;
+mod methods;
+pub mod options;
+mod profiler;
+pub mod project;
+mod requests;
+mod setters;
+pub mod swc_utils;
+mod types;
+mod variables;
 mod visitor;
 
+use std::collections::HashMap;
+
+use cache::Cache;
+use compilers::Compilers;
+use diagnostics::{CompileError, Diagnostic};
+use options::TransformOptions;
+use requests::{Failure, Requests};
+use tauri::{async_runtime, AppHandle, Manager, State};
+
+#[tauri::command]
+async fn parse(
+    app: AppHandle,
+    request: u32,
+    code: String,
+) -> Result<swc_utils::Parsed, Failure<Vec<Diagnostic>>> {
+    return requests::cancellable(app, request, move |_, _| swc_utils::parse(code)).await;
+}
+
+#[tauri::command]
+async fn transform(
+    app: AppHandle,
+    request: u32,
+    code: String,
+    options: Option<TransformOptions>,
+) -> Result<swc_utils::Transformed, Failure<CompileError>> {
+    return requests::cancellable(app, request, move |app, _| {
+        let compilers = app.state::<Compilers>();
+        return compilers
+            .with(|compiler| swc_utils::transform(compiler, code, options.unwrap_or_default()));
+    })
+    .await;
+}
+
+#[tauri::command]
+async fn compile_project(
+    app: AppHandle,
+    request: u32,
+    entities: Vec<project::Entity>,
+    options: Option<TransformOptions>,
+) -> Result<project::Bundle, Failure<Vec<project::EntityError>>> {
+    return requests::cancellable(app, request, move |app, cancelled| {
+        let cache = app.state::<Cache>();
+        let compilers = app.state::<Compilers>();
+        let options = options.unwrap_or_default();
+        return project::compile_project(entities, options, &cache, &compilers, cancelled);
+    })
+    .await;
+}
+
+#[tauri::command]
+fn cancel(requests: State<'_, Requests>, request: u32) {
+    requests.cancel(request);
+}
+
 #[tauri::command]
-fn parse(code: String) -> Result<swc_utils::Parsed, ()> {
-    return swc_utils::parse(code);
+fn check(code: String, stage: bool, globals: Option<Vec<String>>) -> Vec<Diagnostic> {
+    return checker::check(code, stage, globals);
 }
 
 #[tauri::command]
-fn transform(code: String) -> Result<String, ()> {
-    return swc_utils::transform(code).map_err(|_| ());
+async fn variables(
+    app: AppHandle,
+    request: u32,
+    code: String,
+) -> Result<variables::Declared, Failure<Vec<Diagnostic>>> {
+    return requests::cancellable(app, request, move |_, _| swc_utils::get_variables(code)).await;
 }
 
 #[tauri::command]
-fn variables(code: String) -> Result<Vec<(String, Vec<String>)>, ()> {
-    return swc_utils::get_variables(code);
+fn profile(
+    sites: HashMap<String, Vec<profiler::Site>>,
+    samples: Vec<profiler::Sample>,
+) -> Vec<profiler::Hotspot> {
+    return profiler::report(sites, samples);
+}
+
+#[tauri::command]
+fn coverage(entities: HashMap<String, coverage::Session>) -> HashMap<String, coverage::Coverage> {
+    return entities
+        .into_iter()
+        .map(|(name, session)| (name, coverage::report(session)))
+        .collect();
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    // The commands compile on the blocking threads of the runtime.
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .thread_stack_size(project::STACK_SIZE)
+        .build()
+        .expect("failed to build the async runtime");
+    async_runtime::set(runtime.handle().clone());
+
     tauri::Builder::default()
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_fs::init())
-        .invoke_handler(tauri::generate_handler![parse, transform, variables])
+        .manage(Compilers::default())
+        .manage(Requests::default())
+        .setup(|app| {
+            // Compiled entities persist between the sessions,
+            // unless the `persistent-cache` feature is disabled.
+            let path = if cfg!(feature = "persistent-cache") {
+                app.path().app_cache_dir().ok()
+            } else {
+                None
+            };
+            app.manage(Cache::new(path.map(|dir| dir.join("compile-cache.json"))));
+            Ok(())
+        })
+        .invoke_handler(tauri::generate_handler![
+            parse,
+            transform,
+            compile_project,
+            cancel,
+            check,
+            variables,
+            profile,
+            coverage
+        ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
@@ -0,0 +1,88 @@
+use std::{collections::HashMap, sync::OnceLock};
+
+// Generated by build.rs from the methods and functions of static.d.ts
+const METHODS: &str = include_str!(concat!(env!("OUT_DIR"), "/methods.json"));
+
+static TABLE: OnceLock<HashMap<String, bool>> = OnceLock::new();
+
+/// Standard functions and methods missing from static.d.ts, all synchronous.
+/// Array methods get synchronous or awaited callbacks, see `callbacks`.
+const STANDARD: &[&str] = &[
+    "Boolean",
+    "parseInt",
+    "parseFloat",
+    "isNaN",
+    "isFinite",
+    "isArray",
+    "isInteger",
+    "toUpperCase",
+    "toLowerCase",
+    "trim",
+    "trimStart",
+    "trimEnd",
+    "split",
+    "concat",
+    "charAt",
+    "charCodeAt",
+    "startsWith",
+    "endsWith",
+    "padStart",
+    "padEnd",
+    "repeat",
+    "replace",
+    "replaceAll",
+    "substring",
+    "lastIndexOf",
+    "toString",
+    "toFixed",
+    "at",
+    "push",
+    "pop",
+    "shift",
+    "unshift",
+    "splice",
+    "fill",
+    "flat",
+    "keys",
+    "values",
+    "entries",
+    "min",
+    "max",
+    "pow",
+    "sign",
+    "trunc",
+    "hypot",
+    "atan2",
+    "now",
+    "getTime",
+    "stringify",
+    "parse",
+    "assign",
+    "forEach",
+    "map",
+    "filter",
+    "some",
+    "every",
+    "find",
+    "reduce",
+    "sort",
+];
+
+fn table() -> &'static HashMap<String, bool> {
+    return TABLE.get_or_init(|| serde_json::from_str(METHODS).unwrap());
+}
+
+/// Whether a call to `name` may return a promise, that is an engine
+/// method with the `@async` tag, or a function the compiler doesn't know
+pub fn asynchronous(name: &str) -> bool {
+    return match table().get(name) {
+        Some(asynchronous) => *asynchronous,
+        None => !STANDARD.contains(&name),
+    };
+}
+
+/// Whether `name` is an engine method with the `@async` tag,
+/// which gives the engine a chance to render, e.g. `wait`
+pub fn pauses(name: &str) -> bool {
+    return table().get(name) == Some(&true);
+}
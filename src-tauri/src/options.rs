@@ -0,0 +1,59 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+#[serde(default, rename_all = "camelCase")]
+pub struct TransformOptions {
+    /// Emit a source map alongside the code
+    pub source_map: bool,
+    /// Name of the source in the source map, usually the entity name
+    pub file_name: Option<String>,
+    /// Whether the code belongs to the stage
+    pub stage: bool,
+    /// Names of the stage variables, visible to sprites
+    pub globals: Option<Vec<String>>,
+    /// How loops give control back to the engine
+    pub loop_yield: LoopYield,
+    /// Limits against runaway recursion and loops, off if unset
+    pub watchdog: Option<Watchdog>,
+    /// Calls `self.__probe` before every statement
+    pub debug: bool,
+    /// Turns the `/*@block id*/` markers of the blocks
+    /// generator into `self.__running(id)` calls
+    pub highlight_blocks: bool,
+    /// Times every function, event handler and loop with `self.__profile`
+    pub profile: bool,
+    /// Counts the executions of every statement and branch with `self.__cover`
+    pub coverage: bool,
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, Copy, Debug)]
+#[serde(tag = "strategy", rename_all = "camelCase")]
+pub enum LoopYield {
+    /// Wait for the engine in every iteration
+    #[default]
+    Always,
+    /// Wait once per `iterations` iterations
+    Every { iterations: u32 },
+    /// Wait once the loops have run for `milliseconds`
+    Budget { milliseconds: f64 },
+    /// Turbo, loops never wait
+    Never,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Watchdog {
+    /// Maximum number of nested function calls
+    pub max_depth: u32,
+    /// Maximum number of loop iterations without a pause
+    pub max_iterations: u32,
+}
+
+impl Default for Watchdog {
+    fn default() -> Watchdog {
+        return Watchdog {
+            max_depth: 1000,
+            max_iterations: 1_000_000,
+        };
+    }
+}
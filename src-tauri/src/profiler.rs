@@ -0,0 +1,183 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use swc_core::common::{SourceMap, Span};
+
+use crate::diagnostics::{offset, position, Position};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Kind {
+    Function,
+    /// Function passed to an event method, e.g. `self.whenFlag`
+    Handler,
+    Loop,
+}
+
+/// Function or loop timed with `self.__profile(id, milliseconds)`,
+/// where `id` is its index in the table
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Site {
+    pub span: (u32, u32),
+    pub start: Position,
+    pub end: Position,
+    pub kind: Kind,
+    /// Function name, event method of a handler
+    /// or the function enclosing a loop
+    pub name: Option<String>,
+}
+
+impl Site {
+    pub fn new(cm: &SourceMap, span: Span, kind: Kind, name: Option<String>) -> Site {
+        return Site {
+            span: (offset(cm, span.lo), offset(cm, span.hi)),
+            start: position(cm, span.lo),
+            end: position(cm, span.hi),
+            kind,
+            name,
+        };
+    }
+}
+
+/// Counters the engine collected for a site of an entity
+#[derive(Deserialize, Debug)]
+pub struct Sample {
+    pub entity: String,
+    pub site: usize,
+    pub calls: u64,
+    /// Milliseconds spent in the site, including the pauses
+    pub time: f64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Hotspot {
+    pub entity: String,
+    #[serde(flatten)]
+    pub site: Site,
+    pub calls: u64,
+    pub time: f64,
+    /// Milliseconds per call
+    pub average: f64,
+}
+
+/// Ranks the sites by the time spent in them, hottest first.
+/// Samples of unknown sites are ignored.
+pub fn report(sites: HashMap<String, Vec<Site>>, samples: Vec<Sample>) -> Vec<Hotspot> {
+    // The engine may report a site in several samples.
+    let mut totals: HashMap<(String, usize), (u64, f64)> = HashMap::new();
+
+    for sample in samples {
+        let total = totals.entry((sample.entity, sample.site)).or_default();
+        total.0 += sample.calls;
+        total.1 += sample.time;
+    }
+
+    let mut hotspots: Vec<Hotspot> = totals
+        .into_iter()
+        .filter_map(|((entity, id), (calls, time))| {
+            let site = sites.get(&entity)?.get(id)?.clone();
+
+            return Some(Hotspot {
+                entity,
+                site,
+                calls,
+                time,
+                average: if calls > 0 { time / calls as f64 } else { 0.0 },
+            });
+        })
+        .collect();
+
+    hotspots.sort_by(|a, b| {
+        return b
+            .time
+            .total_cmp(&a.time)
+            .then(b.calls.cmp(&a.calls))
+            .then(a.entity.cmp(&b.entity))
+            .then(a.site.span.cmp(&b.site.span));
+    });
+
+    return hotspots;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn site(line: usize, kind: Kind, name: &str) -> Site {
+        return Site {
+            span: (line as u32 * 10, line as u32 * 10 + 5),
+            start: Position { line, column: 0 },
+            end: Position { line, column: 5 },
+            kind,
+            name: Some(name.to_string()),
+        };
+    }
+
+    fn sample(entity: &str, site: usize, calls: u64, time: f64) -> Sample {
+        return Sample {
+            entity: entity.to_string(),
+            site,
+            calls,
+            time,
+        };
+    }
+
+    #[test]
+    fn hotspots_are_the_hottest_sites_first() {
+        let sites = HashMap::from([
+            (
+                "Cat".to_string(),
+                vec![site(1, Kind::Function, "walk"), site(2, Kind::Loop, "walk")],
+            ),
+            ("Dog".to_string(), vec![site(1, Kind::Handler, "whenFlag")]),
+        ]);
+
+        let hotspots = report(
+            sites,
+            vec![
+                sample("Dog", 0, 4, 20.0),
+                sample("Cat", 0, 2, 10.0),
+                sample("Cat", 1, 1, 30.0),
+                sample("Cat", 0, 2, 10.0),
+            ],
+        );
+
+        let order: Vec<(&str, Kind)> = hotspots
+            .iter()
+            .map(|hotspot| (hotspot.entity.as_str(), hotspot.site.kind))
+            .collect();
+
+        // Ties go to the entity name.
+        assert_eq!(
+            order,
+            vec![
+                ("Cat", Kind::Loop),
+                ("Cat", Kind::Function),
+                ("Dog", Kind::Handler)
+            ]
+        );
+
+        // Samples of the same site add up.
+        assert_eq!(hotspots[1].calls, 4);
+        assert_eq!(hotspots[1].time, 20.0);
+        assert_eq!(hotspots[1].average, 5.0);
+    }
+
+    #[test]
+    fn samples_of_unknown_sites_are_ignored() {
+        let sites = HashMap::from([("Cat".to_string(), vec![site(1, Kind::Function, "walk")])]);
+
+        let hotspots = report(
+            sites,
+            vec![
+                sample("Cat", 0, 0, 1.0),
+                sample("Cat", 1, 1, 2.0),
+                sample("Dog", 0, 1, 3.0),
+            ],
+        );
+
+        assert_eq!(hotspots.len(), 1);
+        assert_eq!(hotspots[0].site.name.as_deref(), Some("walk"));
+        assert_eq!(hotspots[0].average, 0.0);
+    }
+}
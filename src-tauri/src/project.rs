@@ -0,0 +1,365 @@
+use std::{
+    collections::HashMap,
+    panic::{self, AssertUnwindSafe},
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    thread,
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+
+use crate::cache::Cache;
+use crate::compilers::Compilers;
+use crate::diagnostics::CompileError;
+use crate::options::TransformOptions;
+use crate::swc_utils::{get_variables, transform, Transformed};
+
+/// swc recurses on nested code, so the compiling threads
+/// get as much stack as the main thread.
+pub const STACK_SIZE: usize = 8 * 1024 * 1024;
+
+/// Sprite or stage, as the frontend describes it
+#[derive(Deserialize, Debug)]
+pub struct Entity {
+    pub name: String,
+    pub code: String,
+    pub stage: bool,
+    /// Initial state, e.g. the position and the size
+    pub init: Map<String, Value>,
+    /// Index of the current costume or backdrop
+    pub current: usize,
+    /// Names and URLs of the costumes or backdrops, in order
+    pub images: Vec<(String, String)>,
+    /// Names and URLs of the sounds, in order
+    pub sounds: Vec<(String, String)>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct EntityError {
+    pub entity: String,
+    #[serde(flatten)]
+    pub error: CompileError,
+}
+
+/// The project failed as a whole, not one of its entities.
+impl From<CompileError> for Vec<EntityError> {
+    fn from(error: CompileError) -> Vec<EntityError> {
+        return vec![EntityError {
+            entity: "Project".to_string(),
+            error,
+        }];
+    }
+}
+
+#[derive(Serialize)]
+pub struct Bundle {
+    /// Script running the whole project on top of the engine
+    pub code: String,
+    /// Index source map of the script, with a section
+    /// for every entity, if the options ask for source maps
+    pub map: Option<String>,
+    /// Compiled entities by name, for the probes, sites and counters
+    pub entities: HashMap<String, Transformed>,
+}
+
+/// Transforms and type-checks the code of an entity,
+/// unless the cache has it compiled with the same options
+fn compile(
+    entity: &Entity,
+    globals: Option<Vec<String>>,
+    options: &TransformOptions,
+    cache: &Cache,
+    compilers: &Compilers,
+) -> Result<Transformed, CompileError> {
+    let options = TransformOptions {
+        file_name: Some(entity.name.clone()),
+        stage: entity.stage,
+        globals,
+        ..options.clone()
+    };
+
+    if let Some(transformed) = cache.get(&entity.code, &options) {
+        return Ok(transformed);
+    }
+
+    // Errors of the type checker stay in the diagnostics,
+    // as they don't stop the entity from running.
+    let transformed =
+        compilers.with(|compiler| transform(compiler, entity.code.clone(), options.clone()))?;
+
+    cache.insert(&entity.code, &options, transformed.clone());
+
+    return Ok(transformed);
+}
+
+/// `{"name": "url", ...}`, keeping the order of the assets
+fn assets(assets: &[(String, String)]) -> String {
+    let entries: Vec<String> = assets
+        .iter()
+        .map(|(name, url)| format!("{}: {}", json(name), json(url)))
+        .collect();
+
+    return format!("{{{}}}", entries.join(", "));
+}
+
+fn json<T: Serialize + ?Sized>(value: &T) -> String {
+    return serde_json::to_string(value).unwrap();
+}
+
+/// Configuration passed to `new Scrap.Sprite` or `new Scrap.Stage`
+fn configuration(entity: &Entity) -> String {
+    let mut properties: Vec<String> = entity
+        .init
+        .iter()
+        .filter(|(key, _)| !matches!(key.as_str(), "current" | "images" | "sounds"))
+        .map(|(key, value)| format!("\t{}: {}", json(key), json(value)))
+        .collect();
+
+    properties.push(format!("\t\"current\": {}", entity.current));
+    properties.push(format!("\t\"images\": {}", assets(&entity.images)));
+    properties.push(format!("\t\"sounds\": {}", assets(&entity.sounds)));
+
+    return format!("{{\n{}\n}}", properties.join(",\n"));
+}
+
+/// Wraps the compiled code of an entity:
+///
+/// ```js
+/// $["Sprite"] = new Scrap.Sprite({...});
+/// $["Sprite"].init(async self => {
+/// // code
+/// });
+/// $["Sprite"].addTo($["Stage"]);
+/// ```
+///
+/// Also returns the line of the wrapped code where the code starts.
+fn wrap(entity: &Entity, code: &str, stage: &str, options: &TransformOptions) -> (String, usize) {
+    let name = json(&entity.name);
+    let reference = format!("$[{}]", name);
+    let class = if entity.stage { "Stage" } else { "Sprite" };
+
+    let mut wrapped = format!(
+        "{} = new Scrap.{}({});\n",
+        reference,
+        class,
+        configuration(entity)
+    );
+
+    if options.highlight_blocks {
+        wrapped += &format!("{}.__running = id => __running({}, id);\n", reference, name);
+    }
+
+    // The code isn't indented, as that would change its template literals.
+    wrapped += &format!("{}.init(async self => {{\n", reference);
+    let line = wrapped.matches('\n').count();
+    wrapped += &format!("{}}});\n", code);
+
+    if !entity.stage {
+        wrapped += &format!("{}.addTo($[{}]);\n", reference, json(stage));
+    }
+
+    return (wrapped, line);
+}
+
+/// Compiles the changed entities in parallel and bundles them into one script.
+/// Fails with the errors of every entity that doesn't compile.
+/// Stops taking new entities once `cancelled` is set.
+pub fn compile_project(
+    entities: Vec<Entity>,
+    options: TransformOptions,
+    cache: &Cache,
+    compilers: &Compilers,
+    cancelled: &AtomicBool,
+) -> Result<Bundle, Vec<EntityError>> {
+    let stage = entities.iter().find(|entity| entity.stage);
+
+    // Sprites can use the variables of the stage.
+    let globals: Option<Vec<String>> = stage
+        .and_then(|stage| get_variables(stage.code.clone()).ok())
+        .map(|declared| declared.variables.into_iter().map(|v| v.name).collect());
+
+    // Every thread compiles the next entity until there are none left.
+    let threads = thread::available_parallelism()
+        .map_or(1, |threads| threads.get())
+        .min(entities.len());
+    let next = AtomicUsize::new(0);
+    let mut results: Vec<Option<Result<Transformed, CompileError>>> =
+        entities.iter().map(|_| None).collect();
+
+    thread::scope(|scope| {
+        let worker = || {
+            let mut compiled = vec![];
+
+            while !cancelled.load(Ordering::Relaxed) {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+
+                let entity = match entities.get(index) {
+                    Some(entity) => entity,
+                    None => return compiled,
+                };
+
+                let globals = if entity.stage { None } else { globals.clone() };
+
+                // A panic fails the entity that caused it, not the whole project.
+                let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                    compile(entity, globals, &options, cache, compilers)
+                }))
+                .unwrap_or_else(|panic| Err(CompileError::panicked(panic)));
+
+                compiled.push((index, result));
+            }
+
+            return compiled;
+        };
+
+        // Fewer threads compile if some can't be spawned.
+        let handles: Vec<_> = (0..threads)
+            .map_while(|_| {
+                thread::Builder::new()
+                    .stack_size(STACK_SIZE)
+                    .spawn_scoped(scope, worker)
+                    .ok()
+            })
+            .collect();
+
+        for handle in handles {
+            if let Ok(compiled) = handle.join() {
+                for (index, result) in compiled {
+                    results[index] = Some(result);
+                }
+            }
+        }
+    });
+
+    cache.persist();
+
+    let stage = stage.map_or("Stage", |stage| stage.name.as_str());
+    let mut code = String::from("var $ = {};\n\n");
+    let mut compiled: HashMap<String, Transformed> = HashMap::new();
+    let mut errors: Vec<EntityError> = vec![];
+    let mut sections: Vec<Value> = vec![];
+
+    for (entity, result) in entities.iter().zip(results) {
+        let result = result.unwrap_or_else(|| {
+            Err(CompileError::internal(
+                "The entity wasn't compiled.".to_string(),
+            ))
+        });
+
+        match result {
+            Ok(transformed) => {
+                let (wrapped, line) = wrap(entity, &transformed.code, stage, &options);
+                let map = transformed.map.as_deref();
+
+                // The map of the entity applies from the line its code starts on.
+                if let Some(map) = map.and_then(|map| serde_json::from_str::<Value>(map).ok()) {
+                    let offset = code.matches('\n').count() + line;
+                    sections.push(json!({"offset": {"line": offset, "column": 0}, "map": map}));
+                }
+
+                code += &wrapped;
+                code += "\n";
+                compiled.insert(entity.name.clone(), transformed);
+            }
+            Err(error) => errors.push(EntityError {
+                entity: entity.name.clone(),
+                error,
+            }),
+        }
+    }
+
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    let map = options
+        .source_map
+        .then(|| json(&json!({"version": 3, "sections": sections})));
+
+    return Ok(Bundle {
+        code,
+        map,
+        entities: compiled,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicBool;
+
+    use serde_json::Map;
+
+    use super::{compile_project, Entity};
+    use crate::cache::Cache;
+    use crate::compilers::Compilers;
+    use crate::diagnostics::Severity;
+    use crate::options::TransformOptions;
+
+    fn entity(name: &str, code: &str, stage: bool) -> Entity {
+        return Entity {
+            name: name.to_string(),
+            code: code.to_string(),
+            stage,
+            init: Map::new(),
+            current: 0,
+            images: vec![],
+            sounds: vec![],
+        };
+    }
+
+    #[test]
+    fn type_errors_dont_fail_the_entity() {
+        let entities = vec![
+            entity("Stage", "", true),
+            entity("Player", "self.move(\"far\");", false),
+        ];
+
+        let bundle = compile_project(
+            entities,
+            Default::default(),
+            &Cache::new(None),
+            &Compilers::default(),
+            &AtomicBool::new(false),
+        )
+        .unwrap();
+
+        assert!(bundle.entities["Player"]
+            .diagnostics
+            .iter()
+            .any(|d| d.severity == Severity::Error));
+        assert!(bundle.code.contains("self.move(\"far\")"));
+    }
+
+    #[test]
+    fn source_maps_of_the_entities_are_sections_of_the_bundle() {
+        let entities = vec![
+            entity("Stage", "self.wait(1);", true),
+            entity("Player", "self.move(10);\nself.say(\"hi\");\n", false),
+        ];
+        let options = TransformOptions {
+            source_map: true,
+            ..Default::default()
+        };
+
+        let bundle = compile_project(
+            entities,
+            options,
+            &Cache::new(None),
+            &Compilers::default(),
+            &AtomicBool::new(false),
+        )
+        .unwrap();
+
+        let map = sourcemap::decode_slice(bundle.map.unwrap().as_bytes()).unwrap();
+        let (line, column) = bundle
+            .code
+            .lines()
+            .enumerate()
+            .find_map(|(line, code)| code.find("self.say").map(|column| (line, column)))
+            .unwrap();
+        let token = map.lookup_token(line as u32, column as u32).unwrap();
+
+        assert_eq!(token.get_source(), Some("Player"));
+        assert_eq!((token.get_src_line(), token.get_src_col()), (1, 0));
+    }
+}
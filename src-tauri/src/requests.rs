@@ -0,0 +1,148 @@
+use std::{
+    collections::HashMap,
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use serde::Serialize;
+use tauri::{async_runtime, AppHandle, Manager};
+
+use crate::diagnostics::CompileError;
+
+/// Rejection of a request, `{"kind": "failed", "error": ...}`
+/// with the error of the command or `{"kind": "cancelled"}`
+#[derive(Serialize, Debug)]
+#[serde(tag = "kind", content = "error", rename_all = "camelCase")]
+pub enum Failure<E> {
+    Failed(E),
+    Cancelled,
+}
+
+/// Cancellation flags of the running requests, by request ID
+#[derive(Default)]
+pub struct Requests {
+    pending: Mutex<HashMap<u32, Arc<AtomicBool>>>,
+}
+
+impl Requests {
+    fn start(&self, request: u32) -> Arc<AtomicBool> {
+        let cancelled = Arc::new(AtomicBool::new(false));
+        self.pending
+            .lock()
+            .unwrap()
+            .insert(request, cancelled.clone());
+        return cancelled;
+    }
+
+    /// Forgets the request, which gets rejected
+    /// if it was cancelled, whatever its result
+    fn finish<T, E>(&self, request: u32, result: Result<T, E>) -> Result<T, Failure<E>> {
+        let cancelled = match self.pending.lock().unwrap().remove(&request) {
+            Some(cancelled) => cancelled.load(Ordering::Relaxed),
+            None => false,
+        };
+
+        if cancelled {
+            return Err(Failure::Cancelled);
+        }
+
+        return result.map_err(Failure::Failed);
+    }
+
+    /// Marks the request as cancelled, so that it stops
+    /// at its next check of the flag and gets rejected.
+    pub fn cancel(&self, request: u32) {
+        if let Some(cancelled) = self.pending.lock().unwrap().get(&request) {
+            cancelled.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Runs `f` on the blocking thread pool, so that the IPC handler
+/// stays free for the other commands while it compiles.
+/// If `f` panics, the request fails with an internal error.
+async fn blocking<T, E, F>(app: AppHandle, f: F) -> Result<T, E>
+where
+    T: Send + 'static,
+    E: From<CompileError> + Send + 'static,
+    F: FnOnce(&AppHandle) -> Result<T, E> + Send + 'static,
+{
+    let result = async_runtime::spawn_blocking(move || {
+        return panic::catch_unwind(AssertUnwindSafe(|| f(&app)))
+            .unwrap_or_else(|panic| Err(CompileError::panicked(panic).into()));
+    })
+    .await;
+
+    return match result {
+        Ok(result) => result,
+        Err(error) => Err(CompileError::internal(error.to_string()).into()),
+    };
+}
+
+/// Runs `f` on the blocking thread pool with the cancellation flag
+/// of the request, which `f` checks to stop early.
+pub async fn cancellable<T, E, F>(app: AppHandle, request: u32, f: F) -> Result<T, Failure<E>>
+where
+    T: Send + 'static,
+    E: From<CompileError> + Send + 'static,
+    F: FnOnce(&AppHandle, &AtomicBool) -> Result<T, E> + Send + 'static,
+{
+    let cancelled = app.state::<Requests>().start(request);
+    let result = blocking(app.clone(), move |app| f(app, &cancelled)).await;
+
+    return app.state::<Requests>().finish(request, result);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::Ordering;
+
+    use super::{Failure, Requests};
+
+    #[test]
+    fn cancelled_requests_are_rejected_as_cancelled() {
+        let requests = Requests::default();
+        let cancelled = requests.start(1);
+
+        requests.cancel(1);
+
+        assert!(cancelled.load(Ordering::Relaxed));
+        assert!(matches!(
+            requests.finish::<_, ()>(1, Ok("compiled")),
+            Err(Failure::Cancelled)
+        ));
+    }
+
+    #[test]
+    fn other_requests_keep_their_result() {
+        let requests = Requests::default();
+        requests.start(1);
+        requests.start(2);
+
+        requests.cancel(2);
+
+        assert!(matches!(
+            requests.finish::<(), _>(1, Err("failed")),
+            Err(Failure::Failed("failed"))
+        ));
+        assert!(matches!(
+            requests.finish::<_, ()>(2, Ok(())),
+            Err(Failure::Cancelled)
+        ));
+    }
+
+    #[test]
+    fn finished_requests_cant_be_cancelled() {
+        let requests = Requests::default();
+        requests.start(1);
+        assert!(matches!(requests.finish::<_, ()>(1, Ok(1)), Ok(1)));
+
+        requests.cancel(1);
+        requests.start(1);
+
+        assert!(matches!(requests.finish::<_, ()>(1, Ok(2)), Ok(2)));
+    }
+}
@@ -0,0 +1,23 @@
+use std::{collections::HashMap, sync::OnceLock};
+
+use serde::Deserialize;
+
+// Generated by build.rs from the `@setter` tags of static.d.ts
+const SETTERS: &str = include_str!(concat!(env!("OUT_DIR"), "/setters.json"));
+
+static TABLE: OnceLock<HashMap<String, Setter>> = OnceLock::new();
+
+#[derive(Deserialize)]
+pub struct Setter {
+    pub method: String,
+    /// Whether the method takes the property name before the value,
+    /// `setX("x", value)` rather than `pointInDirection(value)`
+    pub named: bool,
+}
+
+/// Engine method to call when assigning to `property`
+pub fn setter(property: &str) -> Option<&'static Setter> {
+    return TABLE
+        .get_or_init(|| serde_json::from_str(SETTERS).unwrap())
+        .get(property);
+}
@@ -1,46 +1,53 @@
 use swc_core::{
     atoms::Atom,
-    common::{errors::Handler, sync::Lrc, FileName, FilePathMapping, SourceMap, GLOBALS},
+    common::{
+        comments::{Comments, SingleThreadedComments},
+        sync::Lrc,
+        FileName, FilePathMapping, Mark, SourceMap, Span, Spanned, DUMMY_SP, GLOBALS,
+    },
     ecma::{
         ast::*,
         parser::{lexer::Lexer, Parser, StringInput, Syntax, TsSyntax},
-        transforms::typescript::strip_type,
-        visit::VisitMutWith,
+        transforms::{
+            base::{fixer::fixer, resolver},
+            typescript::{typescript, Config},
+        },
+        visit::{Visit, VisitMutWith, VisitWith},
     },
 };
 
+use crate::checker::Checker;
+use crate::coverage::Counter;
+use crate::debugger::Probe;
+use crate::diagnostics::{Collector, CompileError, Diagnostic};
+use crate::options::TransformOptions;
+use crate::profiler::Site;
+use crate::variables::{variables, Declared, VariableCheck};
 use crate::visitor::javascript;
-use swc::{config::IsModule, Compiler, PrintArgs};
-pub type Parsed = Module;
-
-pub fn get_type(t: &TsType) -> Vec<String> {
-    match t {
-        TsType::TsKeywordType(keyword) => {
-            vec![match keyword.kind {
-                TsKeywordTypeKind::TsNumberKeyword => "number".to_string(),
-                TsKeywordTypeKind::TsBooleanKeyword => "boolean".to_string(),
-                TsKeywordTypeKind::TsStringKeyword => "string".to_string(),
-                TsKeywordTypeKind::TsVoidKeyword => "void".to_string(),
-                _ => "any".to_string(),
-            }]
-        }
-        TsType::TsArrayType(_) => vec!["array".to_string()],
-        TsType::TsParenthesizedType(span) => get_type(&span.type_ann),
-        TsType::TsUnionOrIntersectionType(TsUnionOrIntersectionType::TsUnionType(union)) => {
-            let mut types: Vec<String> = vec![];
+use serde::{Deserialize, Serialize};
+use swc::{
+    config::{IsModule, SourceMapsConfig},
+    Compiler, PrintArgs,
+};
 
-            for type_ann in union.types.iter() {
-                types.extend(get_type(type_ann));
-            }
+#[derive(Serialize)]
+pub struct Parsed {
+    pub module: Module,
+    pub diagnostics: Vec<Diagnostic>,
+}
 
-            return types;
-        }
-        TsType::TsTypeRef(i) => match &i.type_name {
-            TsEntityName::Ident(i) => vec![i.sym.to_string()],
-            _ => vec!["any".to_string()],
-        },
-        _ => vec!["any".to_string()],
-    }
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Transformed {
+    pub code: String,
+    pub map: Option<String>,
+    /// Warnings and the errors of the type checker
+    pub diagnostics: Vec<Diagnostic>,
+    /// Statements instrumented in debug mode, indexed by probe ID
+    pub probes: Vec<Probe>,
+    /// Functions and loops timed by the profiler, indexed by site ID
+    pub sites: Vec<Site>,
+    /// Statements and branches counted for coverage, indexed by counter ID
+    pub counters: Vec<Counter>,
 }
 
 pub fn is_property(node: &MemberExpr, str: &str) -> bool {
@@ -75,8 +82,69 @@ pub fn get_property(node: &MemberExpr) -> Option<Atom> {
     }
 }
 
-pub fn parse(code: String) -> Result<Module, ()> {
+/// Names bound by a pattern
+pub fn bindings(pat: &Pat, names: &mut Vec<Atom>) {
+    match pat {
+        Pat::Ident(binding) => names.push(binding.id.sym.clone()),
+        Pat::Array(array) => {
+            for elem in array.elems.iter().flatten() {
+                bindings(elem, names);
+            }
+        }
+        Pat::Object(object) => {
+            for prop in object.props.iter() {
+                match prop {
+                    ObjectPatProp::KeyValue(kv) => bindings(&kv.value, names),
+                    ObjectPatProp::Assign(assign) => names.push(assign.key.id.sym.clone()),
+                    ObjectPatProp::Rest(rest) => bindings(&rest.arg, names),
+                }
+            }
+        }
+        Pat::Rest(rest) => bindings(&rest.arg, names),
+        Pat::Assign(assign) => bindings(&assign.left, names),
+        _ => {}
+    }
+}
+
+/// First `await` of a function body, not counting nested functions
+pub fn first_await<N: VisitWith<Awaits>>(node: &N) -> Option<Span> {
+    let mut awaits = Awaits(None);
+    node.visit_with(&mut awaits);
+    return awaits.0;
+}
+
+pub struct Awaits(Option<Span>);
+
+impl Visit for Awaits {
+    fn visit_await_expr(&mut self, node: &AwaitExpr) {
+        self.0.get_or_insert(node.span);
+    }
+
+    fn visit_for_of_stmt(&mut self, node: &ForOfStmt) {
+        if node.is_await {
+            self.0.get_or_insert(node.span);
+        }
+
+        node.visit_children_with(self);
+    }
+
+    fn visit_function(&mut self, _: &Function) {}
+
+    fn visit_arrow_expr(&mut self, _: &ArrowExpr) {}
+}
+
+/// Parses ScrapScript with a source map of its own,
+/// so that the spans are relative to the code.
+pub fn parse(code: String) -> Result<Parsed, Vec<Diagnostic>> {
     let cm = Lrc::new(SourceMap::new(FilePathMapping::empty()));
+    return parse_with(&cm, code, None);
+}
+
+pub fn parse_with(
+    cm: &SourceMap,
+    code: String,
+    comments: Option<&dyn Comments>,
+) -> Result<Parsed, Vec<Diagnostic>> {
     let fm = cm.new_source_file(FileName::Custom("main.ts".into()).into(), code.into());
 
     let lexer = Lexer::new(
@@ -89,26 +157,44 @@ pub fn parse(code: String) -> Result<Module, ()> {
         }),
         EsVersion::Es2015,
         StringInput::from(&*fm),
-        None,
+        comments,
     );
 
     let mut parser = Parser::new_from(lexer);
+    let result = parser.parse_module();
 
-    if parser.take_errors().len() > 0 {
-        return Err(());
-    }
+    let mut diagnostics: Vec<Diagnostic> = parser
+        .take_errors()
+        .into_iter()
+        .map(|e| Diagnostic::error(cm, e.span(), e.kind().msg().to_string()))
+        .collect();
 
-    return parser.parse_module().map_err(|_| ());
+    return match result {
+        Ok(module) => Ok(Parsed {
+            module,
+            diagnostics,
+        }),
+        Err(e) => {
+            diagnostics.push(Diagnostic::error(cm, e.span(), e.kind().msg().to_string()));
+            Err(diagnostics)
+        }
+    };
 }
 
-pub fn transform(code: String) -> Result<String, anyhow::Error> {
-    let cm = Lrc::new(SourceMap::new(FilePathMapping::empty()));
-    let compiler = Compiler::new(cm.clone());
+/// Compiles ScrapScript to JavaScript. The compiler can be reused,
+/// as every invocation adds a new file to its source map.
+pub fn transform(
+    compiler: &Compiler,
+    code: String,
+    options: TransformOptions,
+) -> Result<Transformed, CompileError> {
+    let cm = compiler.cm.clone();
 
-    let source = cm.new_source_file(FileName::Custom("main.ts".to_string()).into(), code);
+    let file_name = options.file_name.clone().unwrap_or("main.ts".to_string());
+    let source = cm.new_source_file(FileName::Custom(file_name.clone()).into(), code);
 
-    let handler =
-        Handler::with_emitter_writer(Box::new(std::io::stderr()), Some(compiler.cm.clone()));
+    let collector = Collector::new(cm.clone());
+    let handler = collector.handler();
 
     return GLOBALS.set(&Default::default(), || {
         let program = compiler.parse_js(
@@ -126,50 +212,99 @@ pub fn transform(code: String) -> Result<String, anyhow::Error> {
             Some(compiler.comments()),
         );
 
-        match program {
-            Ok(mut program) => {
-                program.visit_mut_with(&mut javascript());
-                program.visit_mut_with(&mut strip_type());
+        let mut program = match program {
+            Ok(program) => program,
+            Err(_) => return Err(CompileError::Parse(collector.take())),
+        };
 
-                match compiler.print(&program, PrintArgs::default()) {
-                    Ok(s) => Ok(s.code),
-                    Err(e) => Err(e),
-                }
-            }
-            Err(e) => Err(e),
+        let unresolved = Mark::new();
+        let top_level = Mark::new();
+        program.visit_mut_with(&mut resolver(unresolved, top_level, true));
+
+        let declared = variables(&program, Some(compiler.comments()));
+
+        // Errors of the type checker don't stop the compilation,
+        // as the engine checks the values at runtime too.
+        let mut checker = Checker::new(&cm, &program, options.stage);
+        program.visit_with(&mut checker);
+        let mut diagnostics = std::mem::take(&mut checker.diagnostics);
+
+        let mut variables = VariableCheck::new(
+            &cm,
+            &checker,
+            &program,
+            Some(compiler.comments()),
+            options.stage,
+            options.globals.clone(),
+        );
+        program.visit_with(&mut variables);
+        diagnostics.extend(variables.finish());
+
+        let receivers = std::mem::take(&mut checker.receivers);
+
+        // The visitor still sees the TypeScript, e.g. the types of the variables.
+        let mut visitor = javascript(
+            cm.clone(),
+            compiler.comments().clone(),
+            declared,
+            receivers,
+            unresolved,
+            &options,
+        );
+        program.visit_mut_with(&mut visitor);
+
+        if !visitor.unsupported.is_empty() {
+            return Err(CompileError::Unsupported(
+                visitor
+                    .unsupported
+                    .into_iter()
+                    .map(|(span, message)| Diagnostic::error(&cm, span, message))
+                    .collect(),
+            ));
         }
-    });
-}
 
-pub fn get_variables(code: String) -> Result<Vec<(String, Vec<String>)>, ()> {
-    let parsed = parse(code);
-
-    if let Ok(parsed) = parsed {
-        let mut vars: Vec<(String, Vec<String>)> = vec![];
-
-        for stmt in parsed.body.iter() {
-            if let ModuleItem::Stmt(Stmt::Decl(Decl::TsInterface(interface))) = stmt {
-                for member in interface.body.body.iter() {
-                    if let TsTypeElement::TsPropertySignature(prop) = member {
-                        let name = match *prop.key.clone() {
-                            Expr::Ident(ident) => ident.sym.to_string(),
-                            Expr::Lit(Lit::Str(str)) => str.value.to_string(),
-                            _ => continue,
-                        };
-
-                        let type_arr = match prop.type_ann.clone() {
-                            Some(type_ann) => get_type(&type_ann.type_ann),
-                            None => vec!["any".to_string()],
-                        };
-
-                        vars.push((name, type_arr));
-                    }
-                }
-            }
+        // Lowers enums, namespaces and parameter properties
+        program.visit_mut_with(&mut typescript(
+            Config {
+                native_class_properties: true,
+                ..Default::default()
+            },
+            unresolved,
+            top_level,
+        ));
+        // Parenthesizes the inserted awaits, e.g. `(await __map__(list, f)).length`
+        program.visit_mut_with(&mut fixer(None));
+
+        let args = PrintArgs {
+            source_file_name: Some(&file_name),
+            source_map: SourceMapsConfig::Bool(options.source_map),
+            inline_sources_content: true,
+            emit_source_map_columns: true,
+            ..Default::default()
+        };
+
+        match compiler.print(&program, args) {
+            Ok(s) => Ok(Transformed {
+                code: s.code,
+                map: s.map,
+                diagnostics,
+                probes: visitor.probes,
+                sites: visitor.sites,
+                counters: visitor.counters,
+            }),
+            Err(e) => Err(CompileError::Codegen(vec![Diagnostic::error(
+                &cm,
+                DUMMY_SP,
+                e.to_string(),
+            )])),
         }
+    });
+}
 
-        return Ok(vars);
-    }
+pub fn get_variables(code: String) -> Result<Declared, Vec<Diagnostic>> {
+    let cm = Lrc::new(SourceMap::new(FilePathMapping::empty()));
+    let comments = SingleThreadedComments::default();
+    let parsed = parse_with(&cm, code, Some(&comments))?;
 
-    return Err(());
+    return Ok(variables(&Program::Module(parsed.module), Some(&comments)));
 }
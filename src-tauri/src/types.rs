@@ -0,0 +1,439 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use swc_core::ecma::ast::*;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum Type {
+    Any,
+    Unknown,
+    Never,
+    Void,
+    Null,
+    Undefined,
+    Number,
+    String,
+    Boolean,
+    Function,
+    Literal {
+        value: Literal,
+    },
+    Array {
+        element: Box<Type>,
+    },
+    Tuple {
+        elements: Vec<Element>,
+    },
+    Object {
+        properties: Vec<Property>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        index: Option<Box<Index>>,
+    },
+    Union {
+        types: Vec<Type>,
+    },
+    Reference {
+        name: String,
+        arguments: Vec<Type>,
+    },
+    KeyOf {
+        target: Box<Type>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum Literal {
+    Boolean(bool),
+    Number(f64),
+    String(String),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Element {
+    #[serde(rename = "type")]
+    pub ty: Type,
+    pub optional: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Property {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub ty: Type,
+    pub optional: bool,
+    pub readonly: bool,
+}
+
+/// `[key: string]: T`, which lets any other property through
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Index {
+    #[serde(rename = "type")]
+    pub ty: Type,
+    pub readonly: bool,
+}
+
+impl Index {
+    pub fn from_ts(index: &TsIndexSignature) -> Index {
+        return Index {
+            ty: match &index.type_ann {
+                Some(type_ann) => Type::from_ts(&type_ann.type_ann),
+                None => Type::Any,
+            },
+            readonly: index.readonly,
+        };
+    }
+}
+
+fn entity_name(name: &TsEntityName) -> String {
+    match name {
+        TsEntityName::Ident(id) => id.sym.to_string(),
+        TsEntityName::TsQualifiedName(qualified) => {
+            format!("{}.{}", entity_name(&qualified.left), qualified.right.sym)
+        }
+    }
+}
+
+impl Type {
+    pub fn from_ts(t: &TsType) -> Type {
+        match t {
+            TsType::TsKeywordType(keyword) => match keyword.kind {
+                TsKeywordTypeKind::TsNumberKeyword => Type::Number,
+                TsKeywordTypeKind::TsStringKeyword => Type::String,
+                TsKeywordTypeKind::TsBooleanKeyword => Type::Boolean,
+                TsKeywordTypeKind::TsVoidKeyword => Type::Void,
+                TsKeywordTypeKind::TsNullKeyword => Type::Null,
+                TsKeywordTypeKind::TsUndefinedKeyword => Type::Undefined,
+                TsKeywordTypeKind::TsNeverKeyword => Type::Never,
+                TsKeywordTypeKind::TsUnknownKeyword => Type::Unknown,
+                TsKeywordTypeKind::TsObjectKeyword => Type::Object {
+                    properties: vec![],
+                    index: None,
+                },
+                _ => Type::Any,
+            },
+            TsType::TsLitType(lit) => match &lit.lit {
+                TsLit::Number(n) => Type::Literal {
+                    value: Literal::Number(n.value),
+                },
+                TsLit::Str(s) => Type::Literal {
+                    value: Literal::String(s.value.to_string()),
+                },
+                TsLit::Bool(b) => Type::Literal {
+                    value: Literal::Boolean(b.value),
+                },
+                TsLit::Tpl(_) => Type::String,
+                TsLit::BigInt(_) => Type::Any,
+            },
+            TsType::TsArrayType(array) => Type::Array {
+                element: Box::new(Type::from_ts(&array.elem_type)),
+            },
+            TsType::TsTupleType(tuple) => Type::Tuple {
+                elements: tuple
+                    .elem_types
+                    .iter()
+                    .map(|element| match &*element.ty {
+                        TsType::TsOptionalType(optional) => Element {
+                            ty: Type::from_ts(&optional.type_ann),
+                            optional: true,
+                        },
+                        ty => Element {
+                            ty: Type::from_ts(ty),
+                            optional: false,
+                        },
+                    })
+                    .collect(),
+            },
+            TsType::TsTypeLit(lit) => Type::Object {
+                properties: lit
+                    .members
+                    .iter()
+                    .filter_map(|member| match member {
+                        TsTypeElement::TsPropertySignature(prop) => Some(Property {
+                            name: match &*prop.key {
+                                Expr::Ident(id) => id.sym.to_string(),
+                                Expr::Lit(Lit::Str(s)) => s.value.to_string(),
+                                _ => return None,
+                            },
+                            ty: match &prop.type_ann {
+                                Some(type_ann) => Type::from_ts(&type_ann.type_ann),
+                                None => Type::Any,
+                            },
+                            optional: prop.optional,
+                            readonly: prop.readonly,
+                        }),
+                        TsTypeElement::TsMethodSignature(method) => Some(Property {
+                            name: match &*method.key {
+                                Expr::Ident(id) => id.sym.to_string(),
+                                Expr::Lit(Lit::Str(s)) => s.value.to_string(),
+                                _ => return None,
+                            },
+                            ty: Type::Function,
+                            optional: method.optional,
+                            readonly: false,
+                        }),
+                        _ => None,
+                    })
+                    .collect(),
+                index: lit.members.iter().find_map(|member| match member {
+                    TsTypeElement::TsIndexSignature(index) => Some(Box::new(Index::from_ts(index))),
+                    _ => None,
+                }),
+            },
+            TsType::TsTypeRef(reference) => {
+                let name = entity_name(&reference.type_name);
+                let arguments: Vec<Type> = match &reference.type_params {
+                    Some(params) => params.params.iter().map(|t| Type::from_ts(t)).collect(),
+                    None => vec![],
+                };
+
+                if (name == "Array" || name == "ReadonlyArray") && arguments.len() < 2 {
+                    Type::Array {
+                        element: Box::new(arguments.into_iter().next().unwrap_or(Type::Any)),
+                    }
+                } else {
+                    Type::Reference { name, arguments }
+                }
+            }
+            TsType::TsParenthesizedType(paren) => Type::from_ts(&paren.type_ann),
+            TsType::TsOptionalType(optional) => Type::from_ts(&optional.type_ann),
+            TsType::TsUnionOrIntersectionType(TsUnionOrIntersectionType::TsUnionType(union)) => {
+                let mut types: Vec<Type> = vec![];
+
+                for t in union.types.iter() {
+                    match Type::from_ts(t) {
+                        Type::Union { types: nested } => types.extend(nested),
+                        t => types.push(t),
+                    }
+                }
+
+                Type::Union { types }
+            }
+            TsType::TsTypeOperator(operator) => match operator.op {
+                TsTypeOperatorOp::KeyOf => Type::KeyOf {
+                    target: Box::new(Type::from_ts(&operator.type_ann)),
+                },
+                TsTypeOperatorOp::ReadOnly => Type::from_ts(&operator.type_ann),
+                TsTypeOperatorOp::Unique => Type::Any,
+            },
+            TsType::TsFnOrConstructorType(_) => Type::Function,
+            _ => Type::Any,
+        }
+    }
+
+    /// Union of the member values of an enum
+    pub fn from_enum(decl: &TsEnumDecl) -> Type {
+        let mut next = Some(0.0);
+        let mut types: Vec<Type> = vec![];
+
+        for member in decl.members.iter() {
+            let value = match member.init.as_deref() {
+                None => next.map(Literal::Number),
+                Some(Expr::Lit(Lit::Num(n))) => Some(Literal::Number(n.value)),
+                Some(Expr::Lit(Lit::Str(s))) => Some(Literal::String(s.value.to_string())),
+                Some(_) => None,
+            };
+
+            next = match value {
+                Some(Literal::Number(n)) => Some(n + 1.0),
+                _ => None,
+            };
+
+            types.push(match value {
+                Some(value) => Type::Literal { value },
+                // Computed members are numbers.
+                None => Type::Number,
+            });
+        }
+
+        return Type::Union { types };
+    }
+
+    /// Flat list of type names, as used by Blockly checks
+    /// and by `declareVariable` in the engine.
+    pub fn checks(&self) -> Vec<String> {
+        let mut checks: Vec<String> = vec![];
+
+        match self {
+            Type::Number => checks.push("number".to_string()),
+            Type::String => checks.push("string".to_string()),
+            Type::Boolean => checks.push("boolean".to_string()),
+            Type::Void => checks.push("void".to_string()),
+            Type::Array { .. } | Type::Tuple { .. } => checks.push("array".to_string()),
+            Type::Literal { value } => checks.push(
+                match value {
+                    Literal::Boolean(_) => "boolean",
+                    Literal::Number(_) => "number",
+                    Literal::String(_) => "string",
+                }
+                .to_string(),
+            ),
+            Type::Reference { name, .. } => checks.push(name.clone()),
+            Type::Union { types } => {
+                for t in types.iter() {
+                    for check in t.checks() {
+                        if !checks.contains(&check) {
+                            checks.push(check);
+                        }
+                    }
+                }
+            }
+            _ => checks.push("any".to_string()),
+        }
+
+        return checks;
+    }
+}
+
+impl fmt::Display for Literal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Literal::Boolean(b) => write!(f, "{}", b),
+            Literal::Number(n) => write!(f, "{}", n),
+            Literal::String(s) => write!(f, "\"{}\"", s),
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::Any => write!(f, "any"),
+            Type::Unknown => write!(f, "unknown"),
+            Type::Never => write!(f, "never"),
+            Type::Void => write!(f, "void"),
+            Type::Null => write!(f, "null"),
+            Type::Undefined => write!(f, "undefined"),
+            Type::Number => write!(f, "number"),
+            Type::String => write!(f, "string"),
+            Type::Boolean => write!(f, "boolean"),
+            Type::Function => write!(f, "function"),
+            Type::Literal { value } => write!(f, "{}", value),
+            Type::Array { element } => match **element {
+                Type::Union { .. } | Type::KeyOf { .. } => write!(f, "({})[]", element),
+                _ => write!(f, "{}[]", element),
+            },
+            Type::Tuple { elements } => {
+                let elements: Vec<String> = elements
+                    .iter()
+                    .map(|e| format!("{}{}", e.ty, if e.optional { "?" } else { "" }))
+                    .collect();
+
+                write!(f, "[{}]", elements.join(", "))
+            }
+            Type::Object { properties, index } => {
+                let mut properties: Vec<String> = properties
+                    .iter()
+                    .map(|p| {
+                        format!(
+                            "{}{}{}: {}",
+                            if p.readonly { "readonly " } else { "" },
+                            p.name,
+                            if p.optional { "?" } else { "" },
+                            p.ty
+                        )
+                    })
+                    .collect();
+
+                if let Some(index) = index {
+                    properties.push(format!(
+                        "{}[key: string]: {}",
+                        if index.readonly { "readonly " } else { "" },
+                        index.ty
+                    ));
+                }
+
+                if properties.is_empty() {
+                    write!(f, "{{}}")
+                } else {
+                    write!(f, "{{ {} }}", properties.join("; "))
+                }
+            }
+            Type::Union { types } => {
+                let types: Vec<String> = types.iter().map(|t| t.to_string()).collect();
+                write!(f, "{}", types.join(" | "))
+            }
+            Type::Reference { name, arguments } => {
+                if arguments.is_empty() {
+                    write!(f, "{}", name)
+                } else {
+                    let arguments: Vec<String> = arguments.iter().map(|t| t.to_string()).collect();
+                    write!(f, "{}<{}>", name, arguments.join(", "))
+                }
+            }
+            Type::KeyOf { target } => write!(f, "keyof {}", target),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use swc_core::ecma::ast::*;
+
+    use super::{Index, Property, Type};
+    use crate::swc_utils::parse;
+
+    /// Type of `type T = source;`
+    fn ty(source: &str) -> Type {
+        let parsed = parse(format!("type T = {};", source)).unwrap();
+
+        return match &parsed.module.body[0] {
+            ModuleItem::Stmt(Stmt::Decl(Decl::TsTypeAlias(alias))) => {
+                Type::from_ts(&alias.type_ann)
+            }
+            _ => unreachable!(),
+        };
+    }
+
+    #[test]
+    fn object_types_keep_their_modifiers() {
+        assert_eq!(
+            ty("{ readonly max: number; name?: string }"),
+            Type::Object {
+                properties: vec![
+                    Property {
+                        name: "max".to_string(),
+                        ty: Type::Number,
+                        optional: false,
+                        readonly: true,
+                    },
+                    Property {
+                        name: "name".to_string(),
+                        ty: Type::String,
+                        optional: true,
+                        readonly: false,
+                    },
+                ],
+                index: None,
+            }
+        );
+    }
+
+    #[test]
+    fn object_types_keep_their_index_signature() {
+        let object = ty("{ level: number; readonly [key: string]: number | string }");
+
+        assert_eq!(
+            object,
+            Type::Object {
+                properties: vec![Property {
+                    name: "level".to_string(),
+                    ty: Type::Number,
+                    optional: false,
+                    readonly: false,
+                }],
+                index: Some(Box::new(Index {
+                    ty: Type::Union {
+                        types: vec![Type::Number, Type::String]
+                    },
+                    readonly: true,
+                })),
+            }
+        );
+        assert_eq!(
+            object.to_string(),
+            "{ level: number; readonly [key: string]: number | string }"
+        );
+    }
+}
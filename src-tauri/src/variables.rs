@@ -0,0 +1,775 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::{ser::SerializeTuple, Serialize, Serializer};
+use serde_json::{Map, Value};
+use swc_core::{
+    common::{comments::Comments, FileName, SourceMap, Span, Spanned, DUMMY_SP},
+    ecma::{
+        ast::*,
+        parser::{parse_file_as_expr, Syntax},
+        visit::{Visit, VisitWith},
+    },
+};
+
+use crate::checker::{describe, Checker, Ty};
+use crate::diagnostics::Diagnostic;
+use crate::swc_utils::{get_property, is_property};
+use crate::types::{Index, Type};
+
+pub struct Variable {
+    pub name: String,
+    pub ty: Type,
+    pub optional: bool,
+    pub readonly: bool,
+    pub default: Option<Value>,
+    pub span: Span,
+}
+
+#[derive(Serialize)]
+struct Details<'a> {
+    optional: bool,
+    readonly: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    default: &'a Option<Value>,
+}
+
+// Serialized as `[name, checks, type, details]`, so that
+// the frontend can keep using the `[name, checks]` pairs.
+impl Serialize for Variable {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut tuple = serializer.serialize_tuple(4)?;
+        tuple.serialize_element(&self.name)?;
+        tuple.serialize_element(&self.ty.checks())?;
+        tuple.serialize_element(&self.ty)?;
+        tuple.serialize_element(&Details {
+            optional: self.optional,
+            readonly: self.readonly,
+            default: &self.default,
+        })?;
+        return tuple.end();
+    }
+}
+
+/// `{optional, readonly, default}`, the modifiers of a variable
+/// as both the `variables` command and `declareVariable` report them
+pub fn details(optional: bool, readonly: bool, default: &Option<Value>) -> Value {
+    return serde_json::to_value(Details {
+        optional,
+        readonly,
+        default,
+    })
+    .unwrap();
+}
+
+/// Variables declared by a script, as the `variables` command reports them
+#[derive(Serialize, Default)]
+pub struct Declared {
+    pub variables: Vec<Variable>,
+    /// Index signature of `Variables`, which lets any other name through
+    pub index: Option<Index>,
+}
+
+pub struct Declaration<'a> {
+    pub name: &'a str,
+    pub prop: &'a TsPropertySignature,
+    /// Source of the `@default` JSDoc tag
+    pub default: Option<String>,
+}
+
+#[derive(Default)]
+pub struct Declarations<'a> {
+    pub variables: Vec<Declaration<'a>>,
+    /// `[name: string]: T` lets any variable name through
+    pub index: Option<&'a TsIndexSignature>,
+}
+
+impl<'a> Declarations<'a> {
+    pub fn get(&self, name: &str) -> Option<&Declaration<'a>> {
+        return self.variables.iter().find(|d| d.name == name);
+    }
+
+    /// Declared type of the variable, falling back to the index signature
+    pub fn type_of(&self, name: &str) -> Option<&'a TsType> {
+        let type_ann = match self.get(name) {
+            Some(declaration) => declaration.prop.type_ann.as_ref(),
+            None => self.index.and_then(|index| index.type_ann.as_ref()),
+        };
+
+        return type_ann.map(|t| &*t.type_ann);
+    }
+}
+
+pub fn statements(program: &Program) -> Vec<&Stmt> {
+    match program {
+        Program::Script(script) => script.body.iter().collect(),
+        Program::Module(module) => module
+            .body
+            .iter()
+            .filter_map(|item| match item {
+                ModuleItem::Stmt(stmt) => Some(stmt),
+                _ => None,
+            })
+            .collect(),
+    }
+}
+
+struct Collect<'a, 'c> {
+    interfaces: HashMap<&'a str, Vec<&'a TsInterfaceDecl>>,
+    aliases: HashMap<&'a str, &'a TsType>,
+    comments: Option<&'c dyn Comments>,
+    visited: HashSet<&'a str>,
+    declarations: Declarations<'a>,
+}
+
+impl<'a> Collect<'a, '_> {
+    fn named(&mut self, name: &'a str) {
+        if !self.visited.insert(name) {
+            return;
+        }
+
+        // Interfaces merge, their own members shadow inherited ones.
+        if let Some(interfaces) = self.interfaces.get(name).cloned() {
+            for interface in interfaces.iter() {
+                self.members(&interface.body.body);
+            }
+
+            for interface in interfaces.iter() {
+                for parent in interface.extends.iter() {
+                    if let Expr::Ident(id) = &*parent.expr {
+                        self.named(&id.sym);
+                    }
+                }
+            }
+        }
+
+        if let Some(ty) = self.aliases.get(name).copied() {
+            self.ty(ty);
+        }
+    }
+
+    fn ty(&mut self, ty: &'a TsType) {
+        match ty {
+            TsType::TsTypeLit(lit) => self.members(&lit.members),
+            TsType::TsParenthesizedType(paren) => self.ty(&paren.type_ann),
+            TsType::TsUnionOrIntersectionType(TsUnionOrIntersectionType::TsIntersectionType(
+                intersection,
+            )) => {
+                for ty in intersection.types.iter() {
+                    self.ty(ty);
+                }
+            }
+            TsType::TsTypeRef(TsTypeRef {
+                type_name: TsEntityName::Ident(id),
+                ..
+            }) => self.named(&id.sym),
+            _ => {}
+        }
+    }
+
+    fn members(&mut self, members: &'a [TsTypeElement]) {
+        for member in members.iter() {
+            match member {
+                TsTypeElement::TsPropertySignature(prop) => {
+                    let name: &str = match &*prop.key {
+                        Expr::Ident(ident) => &ident.sym,
+                        Expr::Lit(Lit::Str(str)) => &str.value,
+                        _ => continue,
+                    };
+
+                    if self.declarations.get(name).is_none() {
+                        self.declarations.variables.push(Declaration {
+                            name,
+                            prop,
+                            default: self.default(prop.span),
+                        });
+                    }
+                }
+                TsTypeElement::TsIndexSignature(index) => {
+                    self.declarations.index.get_or_insert(index);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn default(&self, span: Span) -> Option<String> {
+        let comments = self.comments?.get_leading(span.lo)?;
+
+        for comment in comments.iter().rev() {
+            for line in comment.text.lines() {
+                let line = line.trim().trim_start_matches('*').trim();
+
+                if let Some(value) = line.strip_prefix("@default") {
+                    return Some(value.trim().to_string());
+                }
+            }
+        }
+
+        return None;
+    }
+}
+
+/// Members of `Variables`, be it an interface (merged and extended)
+/// or a type alias of object types and their intersections.
+pub fn declarations<'a>(program: &'a Program, comments: Option<&dyn Comments>) -> Declarations<'a> {
+    let mut collect = Collect {
+        interfaces: HashMap::new(),
+        aliases: HashMap::new(),
+        comments,
+        visited: HashSet::new(),
+        declarations: Declarations::default(),
+    };
+
+    for stmt in statements(program) {
+        match stmt {
+            Stmt::Decl(Decl::TsInterface(interface)) => collect
+                .interfaces
+                .entry(&interface.id.sym)
+                .or_default()
+                .push(interface),
+            Stmt::Decl(Decl::TsTypeAlias(alias)) => {
+                collect.aliases.insert(&alias.id.sym, &alias.type_ann);
+            }
+            _ => {}
+        }
+    }
+
+    collect.named("Variables");
+
+    return collect.declarations;
+}
+
+/// Parses a `@default` value, which must be a literal
+pub fn literal(source: &str) -> Option<Value> {
+    let cm = SourceMap::default();
+    let fm = cm.new_source_file(FileName::Anon.into(), source.to_string());
+    let expr = parse_file_as_expr(
+        &fm,
+        Syntax::Typescript(Default::default()),
+        EsVersion::Es2015,
+        None,
+        &mut vec![],
+    )
+    .ok()?;
+
+    return value(&expr);
+}
+
+fn number(n: f64) -> Option<Value> {
+    if n.fract() == 0.0 && n.abs() < i64::MAX as f64 {
+        return Some(Value::from(n as i64));
+    }
+
+    return serde_json::Number::from_f64(n).map(Value::Number);
+}
+
+fn value(expr: &Expr) -> Option<Value> {
+    match expr {
+        Expr::Lit(Lit::Num(n)) => number(n.value),
+        Expr::Lit(Lit::Str(s)) => Some(Value::String(s.value.to_string())),
+        Expr::Lit(Lit::Bool(b)) => Some(Value::Bool(b.value)),
+        Expr::Lit(Lit::Null(_)) => Some(Value::Null),
+        Expr::Unary(UnaryExpr {
+            op: UnaryOp::Minus,
+            arg,
+            ..
+        }) => match &**arg {
+            Expr::Lit(Lit::Num(n)) => number(-n.value),
+            _ => None,
+        },
+        Expr::Paren(paren) => value(&paren.expr),
+        Expr::Array(array) => array
+            .elems
+            .iter()
+            .map(|elem| match elem {
+                Some(ExprOrSpread { spread: None, expr }) => value(expr),
+                _ => None,
+            })
+            .collect::<Option<Vec<Value>>>()
+            .map(Value::Array),
+        Expr::Object(object) => object
+            .props
+            .iter()
+            .map(|prop| match prop {
+                PropOrSpread::Prop(prop) => match &**prop {
+                    Prop::KeyValue(kv) => {
+                        let key = match &kv.key {
+                            PropName::Ident(id) => id.sym.to_string(),
+                            PropName::Str(s) => s.value.to_string(),
+                            _ => return None,
+                        };
+
+                        Some((key, value(&kv.value)?))
+                    }
+                    _ => None,
+                },
+                _ => None,
+            })
+            .collect::<Option<Map<String, Value>>>()
+            .map(Value::Object),
+        _ => None,
+    }
+}
+
+/// Turns a `@default` value back into an expression
+pub fn expression(value: &Value) -> Expr {
+    match value {
+        Value::Null => Expr::Lit(Lit::Null(Null { span: DUMMY_SP })),
+        Value::Bool(b) => Expr::Lit(Lit::Bool(Bool {
+            span: DUMMY_SP,
+            value: *b,
+        })),
+        Value::Number(n) => {
+            let n = n.as_f64().unwrap_or_default();
+            let lit = Expr::Lit(Lit::Num(Number {
+                span: DUMMY_SP,
+                value: n.abs(),
+                raw: None,
+            }));
+
+            if n < 0.0 {
+                Expr::Unary(UnaryExpr {
+                    span: DUMMY_SP,
+                    op: UnaryOp::Minus,
+                    arg: Box::new(lit),
+                })
+            } else {
+                lit
+            }
+        }
+        Value::String(s) => Expr::Lit(Lit::Str(Str {
+            span: DUMMY_SP,
+            value: s.as_str().into(),
+            raw: None,
+        })),
+        Value::Array(array) => Expr::Array(ArrayLit {
+            span: DUMMY_SP,
+            elems: array
+                .iter()
+                .map(|v| {
+                    Some(ExprOrSpread {
+                        spread: None,
+                        expr: Box::new(expression(v)),
+                    })
+                })
+                .collect(),
+        }),
+        Value::Object(object) => Expr::Object(ObjectLit {
+            span: DUMMY_SP,
+            props: object
+                .iter()
+                .map(|(key, v)| {
+                    PropOrSpread::Prop(Box::new(Prop::KeyValue(KeyValueProp {
+                        key: PropName::Str(Str {
+                            span: DUMMY_SP,
+                            value: key.as_str().into(),
+                            raw: None,
+                        }),
+                        value: Box::new(expression(v)),
+                    })))
+                })
+                .collect(),
+        }),
+    }
+}
+
+/// Replaces the references to the enums of the script with their values
+fn enumerate(ty: Type, enums: &HashMap<&str, Type>) -> Type {
+    return match ty {
+        Type::Reference { name, arguments } => match enums.get(name.as_str()) {
+            Some(values) if arguments.is_empty() => values.clone(),
+            _ => Type::Reference { name, arguments },
+        },
+        Type::Array { element } => Type::Array {
+            element: Box::new(enumerate(*element, enums)),
+        },
+        Type::Union { types } => Type::Union {
+            types: types.into_iter().map(|t| enumerate(t, enums)).collect(),
+        },
+        ty => ty,
+    };
+}
+
+pub fn variables(program: &Program, comments: Option<&dyn Comments>) -> Declared {
+    let enums: HashMap<&str, Type> = statements(program)
+        .into_iter()
+        .filter_map(|stmt| match stmt {
+            Stmt::Decl(Decl::TsEnum(decl)) => Some((decl.id.sym.as_str(), Type::from_enum(decl))),
+            _ => None,
+        })
+        .collect();
+
+    let declarations = declarations(program, comments);
+
+    let variables = declarations
+        .variables
+        .into_iter()
+        .map(|declaration| Variable {
+            name: declaration.name.to_string(),
+            ty: match &declaration.prop.type_ann {
+                Some(type_ann) => enumerate(Type::from_ts(&type_ann.type_ann), &enums),
+                None => Type::Any,
+            },
+            optional: declaration.prop.optional,
+            readonly: declaration.prop.readonly,
+            default: declaration.default.as_deref().and_then(literal),
+            span: declaration.prop.span,
+        })
+        .collect();
+
+    let index = declarations.index.map(Index::from_ts).map(|index| Index {
+        ty: enumerate(index.ty, &enums),
+        ..index
+    });
+
+    return Declared { variables, index };
+}
+
+/// If the expression is `self.variables.name`, returns the name
+fn variable(node: &MemberExpr) -> Option<String> {
+    if let Expr::Member(variables) = &*node.obj {
+        if let Expr::Ident(id) = &*variables.obj {
+            if id.sym == "self" && is_property(variables, "variables") {
+                return get_property(node).map(|atom| atom.to_string());
+            }
+        }
+    }
+
+    return None;
+}
+
+pub struct VariableCheck<'a> {
+    cm: &'a SourceMap,
+    checker: &'a Checker<'a>,
+    declared: Declarations<'a>,
+    globals: Option<HashSet<String>>,
+    stage: bool,
+    used: HashSet<String>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> VariableCheck<'a> {
+    /// `globals` are the names of the stage variables, which sprites
+    /// can access, too. If they are unknown, undeclared variables
+    /// are reported only for the stage.
+    pub fn new(
+        cm: &'a SourceMap,
+        checker: &'a Checker<'a>,
+        program: &'a Program,
+        comments: Option<&dyn Comments>,
+        stage: bool,
+        globals: Option<Vec<String>>,
+    ) -> VariableCheck<'a> {
+        let mut check = VariableCheck {
+            cm,
+            checker,
+            declared: declarations(program, comments),
+            globals: globals.map(|names| names.into_iter().collect()),
+            stage,
+            used: HashSet::new(),
+            diagnostics: vec![],
+        };
+
+        check.defaults();
+
+        return check;
+    }
+
+    fn defaults(&mut self) {
+        for declaration in self.declared.variables.iter() {
+            let source = match &declaration.default {
+                Some(source) => source,
+                None => continue,
+            };
+
+            let value = match literal(source) {
+                Some(value) => value,
+                None => {
+                    self.diagnostics.push(Diagnostic::error(
+                        self.cm,
+                        declaration.prop.span,
+                        format!(
+                            "The @default value of variable '{}' must be a literal.",
+                            declaration.name
+                        ),
+                    ));
+                    continue;
+                }
+            };
+
+            let target = match &declaration.prop.type_ann {
+                Some(type_ann) => &*type_ann.type_ann,
+                None => continue,
+            };
+
+            let source = self.checker.infer(&expression(&value));
+
+            if !self.checker.assignable(&source, target) {
+                self.diagnostics.push(Diagnostic::error(
+                    self.cm,
+                    declaration.prop.span,
+                    format!(
+                        "Default value of type '{}' is not assignable to variable '{}' of type '{}'.",
+                        describe(&source),
+                        declaration.name,
+                        Type::from_ts(target)
+                    ),
+                ));
+            }
+        }
+    }
+
+    fn access(&mut self, name: String, span: Span) {
+        let known = self.declared.get(&name).is_some()
+            || self.declared.index.is_some()
+            || match &self.globals {
+                Some(globals) => globals.contains(&name),
+                None => !self.stage,
+            };
+
+        if !known {
+            self.diagnostics.push(Diagnostic::error(
+                self.cm,
+                span,
+                format!(
+                    "Variable '{}' is not declared in the Variables interface.",
+                    name
+                ),
+            ));
+        }
+
+        self.used.insert(name);
+    }
+
+    fn readonly(&mut self, name: &str, span: Span) -> bool {
+        let readonly = matches!(self.declared.get(name), Some(d) if d.prop.readonly);
+
+        if readonly {
+            self.diagnostics.push(Diagnostic::error(
+                self.cm,
+                span,
+                format!(
+                    "Cannot assign to '{}' because it is a read-only variable.",
+                    name
+                ),
+            ));
+        }
+
+        return readonly;
+    }
+
+    pub fn finish(mut self) -> Vec<Diagnostic> {
+        // Stage variables are shared with sprites,
+        // so their usage can't be told from here.
+        if !self.stage {
+            for declaration in self.declared.variables.iter() {
+                if !self.used.contains(declaration.name) {
+                    self.diagnostics.push(Diagnostic::warning(
+                        self.cm,
+                        declaration.prop.span,
+                        format!(
+                            "Variable '{}' is declared but never used.",
+                            declaration.name
+                        ),
+                    ));
+                }
+            }
+        }
+
+        return self.diagnostics;
+    }
+}
+
+impl Visit for VariableCheck<'_> {
+    fn visit_member_expr(&mut self, node: &MemberExpr) {
+        node.visit_children_with(self);
+
+        if let Some(name) = variable(node) {
+            self.access(name, node.prop.span());
+        }
+    }
+
+    fn visit_call_expr(&mut self, node: &CallExpr) {
+        node.visit_children_with(self);
+
+        if let Callee::Expr(callee) = &node.callee {
+            if let Expr::Member(member) = &**callee {
+                if is_property(member, "showVariable") || is_property(member, "hideVariable") {
+                    if let Some(Expr::Lit(Lit::Str(name))) = node.args.first().map(|a| &*a.expr) {
+                        self.access(name.value.to_string(), name.span);
+                    }
+                }
+            }
+        }
+    }
+
+    fn visit_assign_expr(&mut self, node: &AssignExpr) {
+        node.visit_children_with(self);
+
+        let member = match &node.left {
+            AssignTarget::Simple(SimpleAssignTarget::Member(member)) => member,
+            _ => return,
+        };
+
+        let name = match variable(member) {
+            Some(name) => name,
+            None => return,
+        };
+
+        if self.readonly(&name, member.span) {
+            return;
+        }
+
+        let target = match self.declared.type_of(&name) {
+            Some(target) => target,
+            None => return,
+        };
+
+        let source = match node.op {
+            AssignOp::Assign => self.checker.infer(&node.right),
+            AssignOp::AddAssign => match self.checker.infer(&node.right) {
+                Ty::String(_) => Ty::String(None),
+                _ => return,
+            },
+            _ => return,
+        };
+
+        if !self.checker.assignable(&source, target) {
+            self.diagnostics.push(Diagnostic::error(
+                self.cm,
+                node.right.span(),
+                format!(
+                    "Type '{}' is not assignable to variable '{}' of type '{}'.",
+                    describe(&source),
+                    name,
+                    Type::from_ts(target)
+                ),
+            ));
+        }
+    }
+
+    fn visit_update_expr(&mut self, node: &UpdateExpr) {
+        node.visit_children_with(self);
+
+        if let Expr::Member(member) = &*node.arg {
+            if let Some(name) = variable(member) {
+                self.readonly(&name, member.span);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compilers::Compilers;
+    use crate::diagnostics::Severity;
+    use crate::options::TransformOptions;
+    use crate::swc_utils::{get_variables, transform, Transformed};
+
+    fn compile(code: &str, stage: bool) -> Transformed {
+        let options = TransformOptions {
+            stage,
+            ..Default::default()
+        };
+
+        return Compilers::default()
+            .with(|compiler| transform(compiler, code.to_string(), options))
+            .unwrap();
+    }
+
+    fn messages(transformed: &Transformed, severity: Severity) -> Vec<&str> {
+        return transformed
+            .diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.severity == severity)
+            .map(|diagnostic| diagnostic.message.as_str())
+            .collect();
+    }
+
+    #[test]
+    fn undeclared_variables_are_errors() {
+        let transformed = compile("self.variables.score = 1;", true);
+
+        assert_eq!(
+            messages(&transformed, Severity::Error),
+            ["Variable 'score' is not declared in the Variables interface."]
+        );
+        assert!(transformed.code.contains("setVariable"));
+    }
+
+    #[test]
+    fn values_of_other_types_are_errors() {
+        let transformed = compile(
+            "interface Variables { n: number; }\nself.variables.n = 'x';",
+            false,
+        );
+
+        assert_eq!(
+            messages(&transformed, Severity::Error),
+            ["Type 'string' is not assignable to variable 'n' of type 'number'."]
+        );
+        assert!(transformed.code.contains("setVariable"));
+    }
+
+    #[test]
+    fn readonly_variables_cant_be_assigned() {
+        let transformed = compile(
+            "interface Variables { readonly n: number; }\nself.variables.n = 1;\nself.variables.n++;",
+            false,
+        );
+
+        assert_eq!(
+            messages(&transformed, Severity::Error),
+            [
+                "Cannot assign to 'n' because it is a read-only variable.",
+                "Cannot assign to 'n' because it is a read-only variable."
+            ]
+        );
+    }
+
+    #[test]
+    fn unused_variables_are_warnings() {
+        let transformed = compile(
+            "interface Variables { n: number; m: number; }\nself.variables.n = 1;",
+            false,
+        );
+
+        assert_eq!(
+            messages(&transformed, Severity::Warning),
+            ["Variable 'm' is declared but never used."]
+        );
+        assert!(messages(&transformed, Severity::Error).is_empty());
+    }
+
+    #[test]
+    fn modifiers_and_the_index_signature_are_reported() {
+        let code = "interface Variables {\n\treadonly max: number;\n\tname?: string;\n\t[key: string]: number;\n}\n";
+        let declared = get_variables(code.to_string()).unwrap();
+
+        assert_eq!(
+            serde_json::to_string(&declared).unwrap(),
+            concat!(
+                r#"{"variables":[["max",["number"],{"kind":"number"},{"optional":false,"readonly":true}],"#,
+                r#"["name",["string"],{"kind":"string"},{"optional":true,"readonly":false}]],"#,
+                r#""index":{"type":{"kind":"number"},"readonly":false}}"#
+            )
+        );
+    }
+
+    #[test]
+    fn declarations_get_the_same_modifiers() {
+        let transformed = compile(
+            "interface Variables {\n\treadonly max: number;\n\t[key: string]: number;\n}\n",
+            true,
+        );
+        let code: String = transformed.code.split_whitespace().collect();
+
+        assert_eq!(
+            code,
+            concat!(
+                r#"self.declareVariable("max",{"kind":"number"},{"optional":false,"readonly":true});"#,
+                r#"self.declareVariable(null,{"kind":"number"},{"optional":true,"readonly":false});"#
+            )
+        );
+    }
+}
@@ -1,465 +1,2157 @@
 use swc_core::{
     atoms::Atom,
-    common::DUMMY_SP,
+    common::{
+        comments::Comments, sync::Lrc, util::take::Take, Mark, SourceMap, Span, Spanned,
+        SyntaxContext, DUMMY_SP,
+    },
     ecma::{
         ast::*,
-        visit::{VisitMut, VisitMutWith},
+        visit::{Visit, VisitMut, VisitMutWith, VisitWith},
     },
 };
 
-use crate::swc_utils::{get_property, get_type, is_property};
+use swc::SwcComments;
+
+use crate::callbacks;
+use crate::checker::Receivers;
+use crate::coverage::{self, Counter};
+use crate::debugger::Probe;
+use crate::diagnostics::position;
+use crate::methods;
+use crate::options::{LoopYield, TransformOptions, Watchdog};
+use crate::profiler::{Kind, Site};
+use crate::setters;
+use crate::swc_utils::{bindings, first_await, get_property, is_property, Awaits};
+use crate::variables::{details, expression, Declared};
+
+/// The program has to be resolved with `unresolved` first.
+pub fn javascript(
+    cm: Lrc<SourceMap>,
+    comments: SwcComments,
+    variables: Declared,
+    receivers: Receivers,
+    unresolved: Mark,
+    options: &TransformOptions,
+) -> JavaScript {
+    return JavaScript {
+        cm,
+        comments,
+        variables,
+        receivers,
+        unresolved: SyntaxContext::empty().apply_mark(unresolved),
+        loop_yield: options.loop_yield,
+        watchdog: options.watchdog,
+        debug: options.debug,
+        highlight_blocks: options.highlight_blocks,
+        profile: options.profile,
+        coverage: options.coverage,
+        ..Default::default()
+    };
+}
+
+fn ident(sym: &str) -> Box<Expr> {
+    return Box::new(Expr::Ident(Ident {
+        sym: sym.into(),
+        ..Default::default()
+    }));
+}
+
+fn member(obj: Box<Expr>, prop: &str) -> Box<Expr> {
+    return Box::new(Expr::Member(MemberExpr {
+        span: DUMMY_SP,
+        obj,
+        prop: MemberProp::Ident(IdentName {
+            sym: prop.into(),
+            ..Default::default()
+        }),
+    }));
+}
+
+fn number(value: f64) -> Box<Expr> {
+    return Box::new(Expr::Lit(Lit::Num(Number {
+        span: DUMMY_SP,
+        value,
+        raw: None,
+    })));
+}
+
+fn null() -> Box<Expr> {
+    return Box::new(Expr::Lit(Lit::Null(Null { span: DUMMY_SP })));
+}
+
+/// `name = value`
+fn assignment(name: &str, value: Box<Expr>) -> Box<Expr> {
+    return Box::new(Expr::Assign(AssignExpr {
+        op: AssignOp::Assign,
+        left: AssignTarget::Simple(SimpleAssignTarget::Ident(BindingIdent {
+            id: Ident {
+                sym: name.into(),
+                ..Default::default()
+            },
+            type_ann: None,
+        })),
+        right: value,
+        ..Default::default()
+    }));
+}
+
+/// `name = value;`
+fn assign(name: &str, value: Box<Expr>) -> Stmt {
+    return Stmt::Expr(ExprStmt {
+        span: DUMMY_SP,
+        expr: assignment(name, value),
+    });
+}
+
+/// `let name = init, ...;`
+fn declare<const N: usize>(vars: [(&str, Box<Expr>); N]) -> Stmt {
+    return Stmt::Decl(Decl::Var(Box::new(VarDecl {
+        kind: VarDeclKind::Let,
+        decls: vars
+            .into_iter()
+            .map(|(name, init)| VarDeclarator {
+                span: DUMMY_SP,
+                name: Pat::Ident(BindingIdent {
+                    id: Ident {
+                        sym: name.into(),
+                        ..Default::default()
+                    },
+                    type_ann: None,
+                }),
+                init: Some(init),
+                definite: false,
+            })
+            .collect(),
+        ..Default::default()
+    })));
+}
+
+/// `const __call__ = { caller };`, the frame of a call, so that the debugger
+/// can step over and out of it. Handlers start a frame without a caller.
+fn frame(caller: Box<Expr>) -> Stmt {
+    return Stmt::Decl(Decl::Var(Box::new(VarDecl {
+        kind: VarDeclKind::Const,
+        decls: vec![VarDeclarator {
+            span: DUMMY_SP,
+            name: Pat::Ident(BindingIdent {
+                id: Ident {
+                    sym: "__call__".into(),
+                    ..Default::default()
+                },
+                type_ann: None,
+            }),
+            init: Some(Box::new(Expr::Object(ObjectLit {
+                span: DUMMY_SP,
+                props: vec![PropOrSpread::Prop(Box::new(Prop::KeyValue(KeyValueProp {
+                    key: PropName::Ident(IdentName {
+                        span: DUMMY_SP,
+                        sym: "caller".into(),
+                    }),
+                    value: caller,
+                })))],
+            }))),
+            definite: false,
+        }],
+        ..Default::default()
+    })));
+}
+
+/// Turns `() => expr` into `() => { return expr; }`
+fn block_body(body: &mut BlockStmtOrExpr) -> &mut BlockStmt {
+    if let BlockStmtOrExpr::Expr(expr) = body {
+        *body = BlockStmtOrExpr::BlockStmt(BlockStmt {
+            stmts: vec![Stmt::Return(ReturnStmt {
+                span: DUMMY_SP,
+                arg: Some(expr.take()),
+            })],
+            ..Default::default()
+        });
+    }
+
+    return match body {
+        BlockStmtOrExpr::BlockStmt(block) => block,
+        BlockStmtOrExpr::Expr(_) => unreachable!(),
+    };
+}
+
+/// `if (++counter > max) throw new Scrap.StoopError(message);`
+fn limit(counter: &str, max: u32, message: String) -> Stmt {
+    return Stmt::If(IfStmt {
+        span: DUMMY_SP,
+        test: Box::new(Expr::Bin(BinExpr {
+            op: BinaryOp::Gt,
+            left: Box::new(Expr::Update(UpdateExpr {
+                span: DUMMY_SP,
+                op: UpdateOp::PlusPlus,
+                prefix: true,
+                arg: ident(counter),
+            })),
+            right: number(max as f64),
+            ..Default::default()
+        })),
+        cons: Box::new(Stmt::Throw(ThrowStmt {
+            span: DUMMY_SP,
+            arg: Box::new(Expr::New(NewExpr {
+                callee: member(ident("Scrap"), "StoopError"),
+                args: Some(vec![ExprOrSpread {
+                    spread: None,
+                    expr: Box::new(Expr::Lit(Lit::Str(Str {
+                        span: DUMMY_SP,
+                        value: message.into(),
+                        raw: None,
+                    }))),
+                }]),
+                ..Default::default()
+            })),
+        })),
+        alt: None,
+    });
+}
+
+/// `performance.now()`
+fn now() -> Box<Expr> {
+    return Box::new(Expr::Call(CallExpr {
+        callee: Callee::Expr(member(ident("performance"), "now")),
+        ..Default::default()
+    }));
+}
+
+/// Block IDs are percent-encoded, so that they can't end the comment
+fn decode(encoded: &str) -> String {
+    let mut bytes: Vec<u8> = vec![];
+    let mut i = 0;
+
+    while i < encoded.len() {
+        if encoded.as_bytes()[i] == b'%' {
+            if let Some(byte) = encoded
+                .get(i + 1..i + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            {
+                bytes.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+
+        bytes.push(encoded.as_bytes()[i]);
+        i += 1;
+    }
+
+    return String::from_utf8_lossy(&bytes).into_owned();
+}
+
+/// Whether `stmt` runs where it is, so that it can be probed or counted
+fn instrumented(stmt: &Stmt) -> bool {
+    if stmt.span().is_dummy() {
+        return false;
+    }
+
+    return !matches!(
+        stmt,
+        Stmt::Empty(_) | Stmt::Decl(Decl::Fn(_) | Decl::TsInterface(_) | Decl::TsTypeAlias(_))
+    );
+}
+
+fn blockify(stmt: &mut Box<Stmt>) {
+    if !matches!(**stmt, Stmt::Block(_)) {
+        let inner = std::mem::replace(&mut **stmt, Stmt::Empty(EmptyStmt { span: DUMMY_SP }));
+
+        **stmt = Stmt::Block(BlockStmt {
+            span: inner.span(),
+            stmts: vec![inner],
+            ..Default::default()
+        });
+    }
+}
+
+fn looping(stmt: &Stmt) -> bool {
+    return match stmt {
+        Stmt::Labeled(labeled) => looping(&labeled.body),
+        Stmt::ForIn(_) | Stmt::ForOf(_) | Stmt::For(_) | Stmt::While(_) | Stmt::DoWhile(_) => true,
+        _ => false,
+    };
+}
+
+/// Names declared in a `for-in` or `for-of` head
+fn head(left: &ForHead) -> Vec<Atom> {
+    let mut names = vec![];
+
+    if let ForHead::VarDecl(var) = left {
+        for decl in var.decls.iter() {
+            bindings(&decl.name, &mut names);
+        }
+    }
+
+    return names;
+}
+
+fn waits<P: VisitWith<Awaits>, B: VisitWith<Awaits>>(params: &P, body: &B) -> bool {
+    return first_await(params).is_some() || first_await(body).is_some();
+}
+
+/// Name of a method, for the watchdog and the profiler
+fn key(key: &PropName) -> Option<String> {
+    return match key {
+        PropName::Ident(id) => Some(id.sym.to_string()),
+        PropName::Str(str) => Some(str.value.to_string()),
+        _ => None,
+    };
+}
+
+/// Functions declared by the statements, including `const` arrow functions
+fn top_level(stmts: &[Stmt]) -> Vec<Id> {
+    let mut functions = vec![];
+
+    for stmt in stmts {
+        match stmt {
+            Stmt::Decl(Decl::Fn(decl)) => functions.push(decl.ident.to_id()),
+            Stmt::Decl(Decl::Var(var)) if var.kind == VarDeclKind::Const => {
+                for decl in var.decls.iter() {
+                    if let (Pat::Ident(binding), Some(Expr::Fn(_) | Expr::Arrow(_))) =
+                        (&decl.name, decl.init.as_deref())
+                    {
+                        functions.push(binding.id.to_id());
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    return functions;
+}
+
+/// Generators of the script. As functions of the script are async,
+/// so are they, and their calls return async iterators.
+#[derive(Default)]
+struct Generators {
+    functions: Vec<Id>,
+    methods: Vec<Atom>,
+    /// Variables initialized with the iterator of a generator
+    iterators: Vec<Id>,
+}
+
+impl Generators {
+    fn method(&mut self, function: &Function, name: Option<Atom>) {
+        if let (true, Some(name)) = (function.is_generator, name) {
+            self.methods.push(name);
+        }
+    }
+
+    /// Whether `expr` calls a generator, possibly awaited,
+    /// or is a variable initialized with such a call
+    fn iterates(&self, expr: &Expr) -> bool {
+        let callee = match expr {
+            Expr::Await(AwaitExpr { arg, .. }) => return self.iterates(arg),
+            Expr::Ident(id) => return self.iterators.contains(&id.to_id()),
+            Expr::Call(CallExpr {
+                callee: Callee::Expr(callee),
+                ..
+            }) => callee,
+            _ => return false,
+        };
+
+        return match &**callee {
+            Expr::Ident(id) => self.functions.contains(&id.to_id()),
+            Expr::Member(member) => match get_property(member) {
+                Some(name) => self.methods.contains(&name),
+                None => false,
+            },
+            _ => false,
+        };
+    }
+}
+
+impl Visit for Generators {
+    fn visit_fn_decl(&mut self, node: &FnDecl) {
+        if node.function.is_generator {
+            self.functions.push(node.ident.to_id());
+        }
+
+        node.visit_children_with(self);
+    }
+
+    fn visit_var_declarator(&mut self, node: &VarDeclarator) {
+        if let (Pat::Ident(binding), Some(Expr::Fn(fn_expr))) = (&node.name, node.init.as_deref()) {
+            if fn_expr.function.is_generator {
+                self.functions.push(binding.id.to_id());
+            }
+        }
+
+        node.visit_children_with(self);
+    }
+
+    fn visit_class_method(&mut self, node: &ClassMethod) {
+        self.method(&node.function, key(&node.key).map(Atom::from));
+        node.visit_children_with(self);
+    }
+
+    fn visit_private_method(&mut self, node: &PrivateMethod) {
+        self.method(&node.function, Some(node.key.name.clone()));
+        node.visit_children_with(self);
+    }
+
+    fn visit_method_prop(&mut self, node: &MethodProp) {
+        self.method(&node.function, key(&node.key).map(Atom::from));
+        node.visit_children_with(self);
+    }
+}
+
+/// Reports the uses of generators other than `for...of`, which can't
+/// tell that the iterators are async, e.g. `[...gen()]`. Collects the
+/// variables they initialize, so that `for...of` can iterate those too.
+struct Iterations<'a> {
+    generators: &'a mut Generators,
+    unsupported: Vec<(Span, String)>,
+}
+
+impl Iterations<'_> {
+    /// Visits `expr` where it may be the iterator of a generator
+    fn iterated(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Await(AwaitExpr { arg, .. }) => self.iterated(arg),
+            _ if self.generators.iterates(expr) => expr.visit_children_with(self),
+            _ => expr.visit_with(self),
+        }
+    }
+}
+
+impl Visit for Iterations<'_> {
+    fn visit_expr(&mut self, node: &Expr) {
+        if self.generators.iterates(node) {
+            self.unsupported.push((
+                node.span(),
+                "Generators are async, so they can only be iterated with for...of".to_string(),
+            ));
+        }
+
+        node.visit_children_with(self);
+    }
+
+    fn visit_for_of_stmt(&mut self, node: &ForOfStmt) {
+        node.left.visit_with(self);
+        self.iterated(&node.right);
+        node.body.visit_with(self);
+    }
+
+    fn visit_var_declarator(&mut self, node: &VarDeclarator) {
+        match (&node.name, node.init.as_deref()) {
+            (Pat::Ident(binding), Some(init)) if self.generators.iterates(init) => {
+                self.generators.iterators.push(binding.id.to_id());
+                self.iterated(init);
+            }
+            _ => node.visit_children_with(self),
+        }
+    }
+
+    // `yield* gen()`
+    fn visit_yield_expr(&mut self, node: &YieldExpr) {
+        match (node.delegate, &node.arg) {
+            (true, Some(arg)) => self.iterated(arg),
+            _ => node.visit_children_with(self),
+        }
+    }
+
+    // `gen().next()`, the methods of the iterator are awaited.
+    fn visit_member_expr(&mut self, node: &MemberExpr) {
+        self.iterated(&node.obj);
+        node.prop.visit_with(self);
+    }
+
+    fn visit_expr_stmt(&mut self, node: &ExprStmt) {
+        self.iterated(&node.expr);
+    }
+}
+
+/// Methods of the script. As they are async, so are their calls,
+/// even if the engine has a synchronous method of the same name.
+#[derive(Default)]
+struct Methods(Vec<Atom>);
+
+impl Methods {
+    fn push(&mut self, name: Option<Atom>) {
+        if let Some(name) = name {
+            self.0.push(name);
+        }
+    }
+
+    fn contains(&self, name: &Atom) -> bool {
+        return self.0.contains(name);
+    }
+}
+
+fn function(expr: &Expr) -> bool {
+    return matches!(expr, Expr::Fn(_) | Expr::Arrow(_));
+}
+
+impl Visit for Methods {
+    fn visit_class_method(&mut self, node: &ClassMethod) {
+        if node.kind == MethodKind::Method {
+            self.push(key(&node.key).map(Atom::from));
+        }
+
+        node.visit_children_with(self);
+    }
+
+    fn visit_method_prop(&mut self, node: &MethodProp) {
+        self.push(key(&node.key).map(Atom::from));
+        node.visit_children_with(self);
+    }
+
+    fn visit_class_prop(&mut self, node: &ClassProp) {
+        if node.value.as_deref().is_some_and(function) {
+            self.push(key(&node.key).map(Atom::from));
+        }
+
+        node.visit_children_with(self);
+    }
+
+    fn visit_key_value_prop(&mut self, node: &KeyValueProp) {
+        if function(&node.value) {
+            self.push(key(&node.key).map(Atom::from));
+        }
+
+        node.visit_children_with(self);
+    }
+
+    // `obj.method = function () {...}`
+    fn visit_assign_expr(&mut self, node: &AssignExpr) {
+        if let AssignTarget::Simple(SimpleAssignTarget::Member(target)) = &node.left {
+            if function(&node.right) {
+                self.push(get_property(target));
+            }
+        }
+
+        node.visit_children_with(self);
+    }
+}
+
+/// `[Symbol.iterator]` of an async generator becomes `[Symbol.asyncIterator]`,
+/// so that `for await` can iterate the object.
+fn iterator(key: &mut PropName, function: &Function) {
+    if !function.is_generator {
+        return;
+    }
+
+    if let PropName::Computed(ComputedPropName { expr, .. }) = key {
+        if let Expr::Member(member) = &mut **expr {
+            if matches!(&*member.obj, Expr::Ident(id) if id.sym == "Symbol")
+                && is_property(member, "iterator")
+            {
+                member.prop = MemberProp::Ident(IdentName {
+                    sym: "asyncIterator".into(),
+                    ..Default::default()
+                });
+            }
+        }
+    }
+}
+
+/// `self` as a parameter
+/// Parameter of a generated function, e.g. `self` of the top-level ones
+fn parameter(name: &str) -> Pat {
+    return Pat::Ident(BindingIdent {
+        id: Ident {
+            sym: name.into(),
+            ..Default::default()
+        },
+        type_ann: None,
+    });
+}
+
+struct Setter {
+    method: Atom,
+    key: Atom,
+    /// Whether the key is of `obj.variables` or `obj.effects`
+    /// rather than of `obj` itself
+    collection: bool,
+    /// Whether the key is passed before the value
+    named: bool,
+}
+
+impl Setter {
+    /// Object to call the setter on, taken out of the target
+    fn object(&self, target: MemberExpr) -> Box<Expr> {
+        return match *target.obj {
+            Expr::Member(collection) if self.collection => collection.obj,
+            obj => Box::new(obj),
+        };
+    }
+
+    /// The target with `obj` in place of its object
+    fn read(&self, target: &MemberExpr, obj: Box<Expr>) -> Box<Expr> {
+        let obj = match &*target.obj {
+            Expr::Member(collection) if self.collection => Box::new(Expr::Member(MemberExpr {
+                obj,
+                ..collection.clone()
+            })),
+            _ => obj,
+        };
+
+        return Box::new(Expr::Member(MemberExpr {
+            obj,
+            ..target.clone()
+        }));
+    }
+}
+
+/// Whether evaluating the expression has no side effects, e.g. `$.Player`
+fn pure(expr: &Expr) -> bool {
+    return match expr {
+        Expr::Ident(_) | Expr::This(_) => true,
+        Expr::Member(member) => {
+            pure(&member.obj)
+                && match &member.prop {
+                    MemberProp::Ident(_) => true,
+                    MemberProp::Computed(computed) => matches!(&*computed.expr, Expr::Lit(_)),
+                    MemberProp::PrivateName(_) => true,
+                }
+        }
+        _ => false,
+    };
+}
+
+/// `f(obj)`, or `await (async (__object__) => f(__object__))(obj)`
+/// if `obj` has side effects, so that `f` can both read and write it
+fn once(obj: Box<Expr>, f: impl FnOnce(Box<Expr>) -> Expr) -> Expr {
+    if pure(&obj) {
+        return f(obj);
+    }
+
+    return Expr::Await(AwaitExpr {
+        span: DUMMY_SP,
+        arg: Box::new(Expr::Call(CallExpr {
+            callee: Callee::Expr(Box::new(Expr::Paren(ParenExpr {
+                span: DUMMY_SP,
+                expr: Box::new(Expr::Arrow(ArrowExpr {
+                    params: vec![parameter("__object__")],
+                    body: Box::new(BlockStmtOrExpr::Expr(Box::new(f(ident("__object__"))))),
+                    is_async: true,
+                    ..Default::default()
+                })),
+            }))),
+            args: vec![ExprOrSpread {
+                spread: None,
+                expr: obj,
+            }],
+            ..Default::default()
+        })),
+    });
+}
+
+#[derive(Default)]
+pub struct JavaScript {
+    cm: Lrc<SourceMap>,
+    comments: SwcComments,
+    /// Declared at the top of the script
+    variables: Declared,
+    /// Sprites, the stage and arrays that the checker found
+    receivers: Receivers,
+    loop_yield: LoopYield,
+    /// Whether a loop of the function being visited
+    /// needs `__iterations__` or `__frame__`
+    loop_state: bool,
+    watchdog: Option<Watchdog>,
+    /// Names of the functions being visited, `None` if anonymous
+    functions: Vec<Option<String>>,
+    /// Name for the next function, if it is anonymous
+    name: Option<String>,
+    /// Whether the next function is declared at the top level
+    top: bool,
+    debug: bool,
+    /// Whether the code being visited can't be async,
+    /// e.g. a constructor, so that its probes aren't awaited
+    sync: bool,
+    /// Whether the next function has to stay synchronous:
+    /// a getter, a setter or an array callback in such code
+    sync_function: bool,
+    /// Locals declared so far, innermost scope last
+    scopes: Vec<Vec<Atom>>,
+    pub probes: Vec<Probe>,
+    highlight_blocks: bool,
+    profile: bool,
+    /// Event method of the next function, if it is a handler
+    handler: Option<String>,
+    /// Whether the next loop is labeled,
+    /// so that the label gets timed and watched with it
+    labeled: bool,
+    /// Loops around the code being visited, in its function
+    loops: usize,
+    pub sites: Vec<Site>,
+    coverage: bool,
+    pub counters: Vec<Counter>,
+    /// Context of the identifiers the script doesn't declare
+    unresolved: SyntaxContext,
+    /// Functions declared at the top level, which take `self`
+    top_level: Vec<Id>,
+    methods: Methods,
+    generators: Generators,
+    /// Whether the innermost function is a generator
+    generator: bool,
+    /// Array methods called with a waiting callback
+    callbacks: Vec<Atom>,
+    pub unsupported: Vec<(Span, String)>,
+}
+
+impl JavaScript {
+    fn unsupported(&mut self, span: Span, message: &str) {
+        self.unsupported.push((span, message.to_string()));
+    }
+
+    fn self_call(&self, span: Span, method: &str, args: Vec<Expr>) -> Expr {
+        return Expr::Call(CallExpr {
+            span,
+            callee: Callee::Expr(Box::new(Expr::Member(MemberExpr {
+                span: DUMMY_SP,
+                obj: Box::new(Expr::Ident(Ident {
+                    sym: "self".into(),
+                    optional: false,
+                    ..Default::default()
+                })),
+                prop: MemberProp::Ident(IdentName {
+                    span: DUMMY_SP,
+                    sym: method.into(),
+                }),
+            }))),
+            args: args
+                .into_iter()
+                .map(|expr| ExprOrSpread {
+                    spread: None,
+                    expr: Box::new(expr),
+                })
+                .collect(),
+            type_args: None,
+            ..Default::default()
+        });
+    }
+
+    fn declarations(&self) -> Vec<Stmt> {
+        let mut stmts: Vec<Stmt> = vec![];
+
+        for variable in self.variables.variables.iter() {
+            let name = Expr::Lit(Lit::Str(Str {
+                span: DUMMY_SP,
+                value: variable.name.as_str().into(),
+                raw: None,
+            }));
+
+            // The structured type and the modifiers, as the `variables` command reports them
+            let ty = serde_json::to_value(&variable.ty).unwrap();
+            let modifiers = details(variable.optional, variable.readonly, &None);
+            let args = vec![name.clone(), expression(&ty), expression(&modifiers)];
+
+            stmts.push(Stmt::Expr(ExprStmt {
+                span: variable.span,
+                expr: Box::new(self.self_call(variable.span, "declareVariable", args)),
+            }));
+
+            if let Some(value) = &variable.default {
+                stmts.push(Stmt::Expr(ExprStmt {
+                    span: variable.span,
+                    expr: Box::new(Expr::Await(AwaitExpr {
+                        span: variable.span,
+                        arg: Box::new(self.self_call(
+                            variable.span,
+                            "setVariable",
+                            vec![name, expression(value)],
+                        )),
+                    })),
+                }));
+            }
+        }
+
+        // `self.declareVariable(null, type, {optional: true, readonly})`
+        // lets any other name through.
+        if let Some(index) = &self.variables.index {
+            let ty = serde_json::to_value(&index.ty).unwrap();
+            let modifiers = details(true, index.readonly, &None);
+            let args = vec![
+                Expr::Lit(Lit::Null(Null { span: DUMMY_SP })),
+                expression(&ty),
+                expression(&modifiers),
+            ];
+
+            stmts.push(Stmt::Expr(ExprStmt {
+                span: DUMMY_SP,
+                expr: Box::new(self.self_call(DUMMY_SP, "declareVariable", args)),
+            }));
+        }
+
+        return stmts;
+    }
+
+    /// Statement starting every loop iteration, so that
+    /// the engine gets a chance to render and handle events
+    fn pause(&mut self) -> Option<Stmt> {
+        let delay = Stmt::Expr(ExprStmt {
+            span: DUMMY_SP,
+            expr: Box::new(Expr::Await(AwaitExpr {
+                span: DUMMY_SP,
+                arg: Box::new(Expr::New(NewExpr {
+                    span: DUMMY_SP,
+                    callee: ident("Promise"),
+                    args: Some(vec![ExprOrSpread {
+                        spread: None,
+                        expr: member(ident("self"), "delay"),
+                    }]),
+                    type_args: None,
+                    ..Default::default()
+                })),
+            })),
+        });
+
+        // The watchdog counts the iterations since the last pause.
+        let mut delay = vec![delay];
+
+        if self.watchdog.is_some() {
+            delay.push(assign("__steps__", number(0.0)));
+        }
+
+        match self.loop_yield {
+            LoopYield::Always => delay.into_iter().next(),
+            LoopYield::Never => None,
+
+            // if (++__iterations__ % n === 0) await new Promise(self.delay);
+            LoopYield::Every { iterations } => {
+                self.loop_state = true;
+
+                Some(Stmt::If(IfStmt {
+                    span: DUMMY_SP,
+                    test: Box::new(Expr::Bin(BinExpr {
+                        op: BinaryOp::EqEqEq,
+                        left: Box::new(Expr::Bin(BinExpr {
+                            op: BinaryOp::Mod,
+                            left: Box::new(Expr::Update(UpdateExpr {
+                                span: DUMMY_SP,
+                                op: UpdateOp::PlusPlus,
+                                prefix: true,
+                                arg: ident("__iterations__"),
+                            })),
+                            right: number(iterations.max(1) as f64),
+                            ..Default::default()
+                        })),
+                        right: number(0.0),
+                        ..Default::default()
+                    })),
+                    cons: Box::new(match delay.len() {
+                        1 => delay.remove(0),
+                        _ => Stmt::Block(BlockStmt {
+                            stmts: delay,
+                            ..Default::default()
+                        }),
+                    }),
+                    alt: None,
+                }))
+            }
+
+            // if (performance.now() - __frame__ >= ms) {
+            //     await new Promise(self.delay);
+            //     __frame__ = performance.now();
+            // }
+            LoopYield::Budget { milliseconds } => {
+                self.loop_state = true;
+
+                Some(Stmt::If(IfStmt {
+                    span: DUMMY_SP,
+                    test: Box::new(Expr::Bin(BinExpr {
+                        op: BinaryOp::GtEq,
+                        left: Box::new(Expr::Bin(BinExpr {
+                            op: BinaryOp::Sub,
+                            left: now(),
+                            right: ident("__frame__"),
+                            ..Default::default()
+                        })),
+                        right: number(milliseconds),
+                        ..Default::default()
+                    })),
+                    cons: Box::new(Stmt::Block(BlockStmt {
+                        stmts: delay
+                            .into_iter()
+                            .chain([assign("__frame__", now())])
+                            .collect(),
+                        ..Default::default()
+                    })),
+                    alt: None,
+                }))
+            }
+        }
+    }
+
+    /// `let __iterations__ = 0;` or `let __frame__ = performance.now();`
+    fn loop_state(&self) -> Stmt {
+        let (name, init) = match self.loop_yield {
+            LoopYield::Budget { .. } => ("__frame__", now()),
+            _ => ("__iterations__", number(0.0)),
+        };
+
+        return declare([(name, init)]);
+    }
+
+    /// Where the code being visited is, for the watchdog messages
+    fn location(&self) -> String {
+        return match self.functions.last() {
+            Some(Some(name)) => format!("function '{}'", name),
+            Some(None) => "an anonymous function".to_string(),
+            None => "the main script".to_string(),
+        };
+    }
+
+    /// Watchdog of the loops, unless they pause in every iteration and can't run away
+    fn runaways(&self) -> Option<Watchdog> {
+        return match self.loop_yield {
+            LoopYield::Always => None,
+            _ => self.watchdog,
+        };
+    }
+
+    /// `if (++__steps__ > max) throw new Scrap.StoopError(...);`
+    fn runaway(&self, watchdog: Watchdog) -> Stmt {
+        return limit(
+            "__steps__",
+            watchdog.max_iterations,
+            format!(
+                "A loop in {} ran {} times without pausing.",
+                self.location(),
+                watchdog.max_iterations
+            ),
+        );
+    }
+
+    /// Counts the call depth in the function body. Every run of a handler starts
+    /// from `let __depth__ = 0;`, top-level functions get the depth of their caller
+    /// as a parameter and check `if (++__depth__ > max) throw ...;`, while
+    /// other functions count in the variable of their scope:
+    ///
+    /// `try { if (++__depth__ > max) throw ...; body } finally { __depth__--; }`
+    fn watch(&self, watchdog: Watchdog, body: &mut BlockStmt, handler: bool, top: bool) {
+        if handler {
+            body.stmts.insert(0, declare([("__depth__", number(0.0))]));
+            return;
+        }
+
+        let limit = limit(
+            "__depth__",
+            watchdog.max_depth,
+            format!(
+                "Maximum call depth of {} exceeded in {}.",
+                watchdog.max_depth,
+                self.location()
+            ),
+        );
+
+        if top {
+            body.stmts.insert(0, limit);
+            return;
+        }
+
+        let mut stmts = vec![limit];
+        stmts.append(&mut body.stmts);
+
+        body.stmts = vec![Stmt::Try(Box::new(TryStmt {
+            span: DUMMY_SP,
+            block: BlockStmt {
+                stmts,
+                ..Default::default()
+            },
+            handler: None,
+            finalizer: Some(BlockStmt {
+                stmts: vec![Stmt::Expr(ExprStmt {
+                    span: DUMMY_SP,
+                    expr: Box::new(Expr::Update(UpdateExpr {
+                        span: DUMMY_SP,
+                        op: UpdateOp::MinusMinus,
+                        prefix: false,
+                        arg: ident("__depth__"),
+                    })),
+                })],
+                ..Default::default()
+            }),
+        }))];
+    }
+
+    /// `let __start__ = performance.now();`
+    /// `try { stmts } finally { self.__profile(id, performance.now() - __start__); }`
+    fn time(
+        &mut self,
+        span: Span,
+        kind: Kind,
+        name: Option<String>,
+        stmts: Vec<Stmt>,
+    ) -> Vec<Stmt> {
+        let id = self.sites.len();
+        self.sites.push(Site::new(&self.cm, span, kind, name));
+
+        let elapsed = Expr::Bin(BinExpr {
+            op: BinaryOp::Sub,
+            left: now(),
+            right: ident("__start__"),
+            ..Default::default()
+        });
+
+        return vec![
+            declare([("__start__", now())]),
+            Stmt::Try(Box::new(TryStmt {
+                span: DUMMY_SP,
+                block: BlockStmt {
+                    stmts,
+                    ..Default::default()
+                },
+                handler: None,
+                finalizer: Some(BlockStmt {
+                    stmts: vec![Stmt::Expr(ExprStmt {
+                        span: DUMMY_SP,
+                        expr: Box::new(self.self_call(
+                            DUMMY_SP,
+                            "__profile",
+                            vec![*number(id as f64), elapsed],
+                        )),
+                    })],
+                    ..Default::default()
+                }),
+            })),
+        ];
+    }
+
+    /// Parameters of top-level functions before their own: `self` and,
+    /// to debug, the frame of their caller and, to watch, the call depth
+    fn parameters(&self) -> Vec<&'static str> {
+        let mut params = vec!["self"];
+
+        if self.debug {
+            params.push("__caller__");
+        }
+
+        if self.watchdog.is_some() {
+            params.push("__depth__");
+        }
+
+        return params;
+    }
+
+    /// Adds the parameters of a top-level function
+    fn receive(&self, function: &mut Function) {
+        if let (true, Some(body)) = (self.debug, &mut function.body) {
+            body.stmts.insert(0, frame(ident("__caller__")));
+        }
+
+        for (index, name) in self.parameters().into_iter().enumerate() {
+            function.params.insert(
+                index,
+                Param {
+                    span: DUMMY_SP,
+                    decorators: vec![],
+                    pat: parameter(name),
+                },
+            );
+        }
+    }
+
+    /// Arguments passed to top-level functions before their own
+    fn receivers(&self) -> Vec<ExprOrSpread> {
+        return self
+            .parameters()
+            .into_iter()
+            .map(|name| match name {
+                "__caller__" => "__call__",
+                name => name,
+            })
+            .map(|name| ExprOrSpread {
+                spread: None,
+                expr: ident(name),
+            })
+            .collect();
+    }
+
+    /// Site of the function being visited
+    fn site(&self, handler: Option<String>) -> (Kind, Option<String>) {
+        return match handler {
+            Some(event) => (Kind::Handler, Some(event)),
+            None => (Kind::Function, self.functions.last().cloned().flatten()),
+        };
+    }
+
+    /// Whether the call may return a promise. Functions and methods of the script
+    /// are async, and so may be anything it declares or the API doesn't know.
+    fn awaited(&self, call: &CallExpr) -> bool {
+        let callee = match &call.callee {
+            Callee::Expr(callee) => callee,
+            _ => return true,
+        };
+
+        return match &**callee {
+            // Generators return their iterator right away.
+            Expr::Ident(id) if self.generators.functions.contains(&id.to_id()) => false,
+            // Whereas the methods of their iterators are async, e.g. `next`.
+            Expr::Member(member) if self.generators.iterates(&member.obj) => true,
+            Expr::Ident(id) => id.ctxt != self.unresolved || methods::asynchronous(&id.sym),
+            Expr::Member(member) => match get_property(member) {
+                Some(name) => self.methods.contains(&name) || methods::asynchronous(&name),
+                None => true,
+            },
+            _ => true,
+        };
+    }
+
+    /// Array method taking a callback that the call is of, e.g. `map` of `list.map(f)`,
+    /// if the checker knows the receiver is an array
+    fn array_method(&self, call: &CallExpr) -> Option<Atom> {
+        let member = match &call.callee {
+            Callee::Expr(callee) => match &**callee {
+                Expr::Member(member) => member,
+                _ => return None,
+            },
+            _ => return None,
+        };
+
+        if !self.receivers.arrays.contains(&member.span) {
+            return None;
+        }
+
+        return get_property(member).filter(|method| callbacks::METHODS.contains(&method.as_str()));
+    }
+
+    /// Keeps the callback of an array method synchronous, or if it waits,
+    /// calls the awaited equivalent: `list.map(f)` to `__map__(list, f)`
+    fn callback(&mut self, call: &mut CallExpr) {
+        let method = match self.array_method(call) {
+            Some(method) => method,
+            None => return,
+        };
+
+        let member = match &mut call.callee {
+            Callee::Expr(callee) => match &mut **callee {
+                Expr::Member(member) => member,
+                _ => return,
+            },
+            _ => return,
+        };
+
+        let waits = match call.args.first_mut().map(|arg| &mut *arg.expr) {
+            Some(Expr::Arrow(arrow)) => {
+                arrow.is_async = waits(&arrow.params, &arrow.body);
+                arrow.is_async
+            }
+            Some(Expr::Fn(fn_expr)) => {
+                let function = &mut fn_expr.function;
+                function.is_async = waits(&function.params, &function.body);
+                function.is_async
+            }
+            Some(Expr::Ident(id)) if id.ctxt == self.unresolved => false,
+            // Functions of the script are async, and so may be other callbacks.
+            Some(_) => true,
+            None => return,
+        };
+
+        if !waits {
+            return;
+        }
+
+        call.args.insert(
+            0,
+            ExprOrSpread {
+                spread: None,
+                expr: member.obj.take(),
+            },
+        );
+
+        call.callee = Callee::Expr(ident(&callbacks::helper(&method)));
+
+        if !self.callbacks.contains(&method) {
+            self.callbacks.push(method);
+        }
+    }
+
+    /// Reports the first wait in the body of a function that can't be async
+    fn synchronous<N: VisitWith<Awaits>>(&mut self, body: &N, function: &str) {
+        if let Some(span) = first_await(body) {
+            self.unsupported(
+                span,
+                &format!(
+                    "{} can't be async, so it can't wait for the engine or call functions of the script.",
+                    function
+                ),
+            );
+        }
+    }
+
+    /// Visits code that can't be async, e.g. a constructor
+    fn visit_sync<N: VisitMutWith<Self>>(&mut self, node: &mut N) {
+        let sync = std::mem::replace(&mut self.sync, true);
+        node.visit_mut_children_with(self);
+        self.sync = sync;
+    }
+
+    /// `await self.__probe(id, line, column, () => ({ ...locals }), __call__);`,
+    /// not awaited in code that can't be async
+    fn probe(&mut self, stmt: &Stmt) -> Option<Stmt> {
+        let span = stmt.span();
+
+        if !self.debug || !instrumented(stmt) {
+            return None;
+        }
+
+        let id = self.probes.len();
+        let start = position(&self.cm, span.lo);
+        let function = self.functions.last().cloned().flatten();
+
+        self.probes.push(Probe::new(&self.cm, span, function));
+
+        let mut locals: Vec<&Atom> = vec![];
+
+        for name in self.scopes.iter().flatten() {
+            if !locals.contains(&name) {
+                locals.push(name);
+            }
+        }
+
+        let locals = Expr::Arrow(ArrowExpr {
+            body: Box::new(BlockStmtOrExpr::Expr(Box::new(Expr::Paren(ParenExpr {
+                span: DUMMY_SP,
+                expr: Box::new(Expr::Object(ObjectLit {
+                    span: DUMMY_SP,
+                    props: locals
+                        .into_iter()
+                        .map(|name| {
+                            PropOrSpread::Prop(Box::new(Prop::Shorthand(Ident {
+                                sym: name.clone(),
+                                ..Default::default()
+                            })))
+                        })
+                        .collect(),
+                })),
+            })))),
+            ..Default::default()
+        });
+
+        let call = self.self_call(
+            span,
+            "__probe",
+            vec![
+                *number(id as f64),
+                *number(start.line as f64),
+                *number(start.column as f64),
+                locals,
+                *ident("__call__"),
+            ],
+        );
+
+        let expr = match self.sync {
+            true => call,
+            false => Expr::Await(AwaitExpr {
+                span,
+                arg: Box::new(call),
+            }),
+        };
+
+        return Some(Stmt::Expr(ExprStmt {
+            span,
+            expr: Box::new(expr),
+        }));
+    }
+
+    /// Takes the `/*@block id*/` marker the blocks generator puts before statements
+    fn block(&mut self, stmt: &Stmt) -> Option<String> {
+        let span = stmt.span();
+
+        if !self.highlight_blocks && !self.coverage || span.is_dummy() {
+            return None;
+        }
+
+        let mut id = None;
+        let mut comments = vec![];
+
+        for comment in self.comments.take_leading(span.lo)? {
+            match comment.text.strip_prefix("@block ") {
+                Some(encoded) if id.is_none() => id = Some(decode(encoded.trim())),
+                _ => comments.push(comment),
+            }
+        }
+
+        if !comments.is_empty() {
+            self.comments.add_leading_comments(span.lo, comments);
+        }
+
+        return id;
+    }
+
+    /// `self.__running("id");`
+    fn running(&self, span: Span, id: &str) -> Stmt {
+        return Stmt::Expr(ExprStmt {
+            span,
+            expr: Box::new(self.self_call(
+                span,
+                "__running",
+                vec![Expr::Lit(Lit::Str(Str {
+                    span: DUMMY_SP,
+                    value: id.into(),
+                    raw: None,
+                }))],
+            )),
+        });
+    }
+
+    /// `self.__cover(id)`
+    fn cover(&mut self, span: Span, kind: coverage::Kind, block: Option<String>) -> Expr {
+        let id = self.counters.len();
+        self.counters
+            .push(Counter::new(&self.cm, span, kind, block));
+
+        return self.self_call(span, "__cover", vec![*number(id as f64)]);
+    }
+
+    /// `self.__cover(id);` before the statements of a branch
+    fn branch(&mut self, span: Span, stmts: &mut Vec<Stmt>) {
+        let expr = self.cover(span, coverage::Kind::Branch, None);
+
+        stmts.insert(
+            0,
+            Stmt::Expr(ExprStmt {
+                span: DUMMY_SP,
+                expr: Box::new(expr),
+            }),
+        );
+    }
+
+    /// `(self.__cover(id), expr)`
+    fn covered(&mut self, expr: Box<Expr>) -> Box<Expr> {
+        let span = expr.span();
+        let cover = self.cover(span, coverage::Kind::Branch, None);
+
+        return Box::new(Expr::Paren(ParenExpr {
+            span: DUMMY_SP,
+            expr: Box::new(Expr::Seq(SeqExpr {
+                span: DUMMY_SP,
+                exprs: vec![Box::new(cover), expr],
+            })),
+        }));
+    }
+
+    /// Adds the names declared by `stmt` to the innermost scope
+    fn declare_locals(&mut self, stmt: &Stmt) {
+        let scope = match self.scopes.last_mut() {
+            Some(scope) => scope,
+            None => return,
+        };
+
+        match stmt {
+            Stmt::Decl(Decl::Var(var)) => {
+                for decl in var.decls.iter() {
+                    bindings(&decl.name, scope);
+                }
+            }
+            Stmt::Decl(Decl::Class(class)) => scope.push(class.ident.sym.clone()),
+            _ => {}
+        }
+    }
+
+    /// Resolves an assignment target to the engine setter, called as
+    /// `obj.method("key", value)` or, if it isn't named, `obj.method(value)`.
+    fn setter(&mut self, target: &MemberExpr) -> Option<Setter> {
+        if let Expr::Member(collection) = &*target.obj {
+            for (name, method, message) in [
+                (
+                    "variables",
+                    "setVariable",
+                    "Variable names must be written literally",
+                ),
+                (
+                    "effects",
+                    "setEffect",
+                    "Effect names must be written literally",
+                ),
+            ] {
+                if is_property(collection, name)
+                    && self.receivers.entities.contains(&collection.span)
+                {
+                    return match get_property(target) {
+                        Some(key) => Some(Setter {
+                            method: method.into(),
+                            key,
+                            collection: true,
+                            named: true,
+                        }),
+                        None => {
+                            self.unsupported(target.prop.span(), message);
+                            None
+                        }
+                    };
+                }
+            }
+        }
+
+        // Other objects may have properties of the same names, e.g. `pos.x`
+        if !self.receivers.entities.contains(&target.span) {
+            return None;
+        }
+
+        let key = get_property(target)?;
+        let setter = setters::setter(&key)?;
+
+        return Some(Setter {
+            method: setter.method.as_str().into(),
+            key,
+            collection: false,
+            named: setter.named,
+        });
+    }
+
+    fn set(&self, span: Span, setter: Setter, obj: Box<Expr>, value: Box<Expr>) -> Expr {
+        let mut args = vec![];
+
+        if setter.named {
+            args.push(ExprOrSpread {
+                spread: None,
+                expr: Box::new(Expr::Lit(Lit::Str(Str {
+                    span: DUMMY_SP,
+                    value: setter.key,
+                    raw: None,
+                }))),
+            });
+        }
+
+        args.push(ExprOrSpread {
+            spread: None,
+            expr: value,
+        });
+
+        return Expr::Await(AwaitExpr {
+            span,
+            arg: Box::new(Expr::Call(CallExpr {
+                span,
+                callee: Callee::Expr(Box::new(Expr::Member(MemberExpr {
+                    span: DUMMY_SP,
+                    obj,
+                    prop: MemberProp::Ident(IdentName {
+                        sym: setter.method,
+                        ..Default::default()
+                    }),
+                }))),
+                args,
+                type_args: None,
+                ..Default::default()
+            })),
+        });
+    }
+
+    /// `++`/`--` through the setter. If the result is `used`, the
+    /// (numeric) old or new value is passed through an async arrow:
+    ///
+    /// `x++` becomes `await (async __value__ => (await set(__value__ + 1), __value__))(+x)`
+    fn update(&mut self, update: &mut UpdateExpr, used: bool) -> Option<Expr> {
+        let setter = match &*update.arg {
+            Expr::Member(target) => self.setter(target)?,
+            _ => return None,
+        };
+
+        let target = match *update.arg.take() {
+            Expr::Member(target) => target,
+            _ => return None,
+        };
+
+        let op = match update.op {
+            UpdateOp::PlusPlus => BinaryOp::Add,
+            UpdateOp::MinusMinus => BinaryOp::Sub,
+        };
+
+        let step = |value: Box<Expr>| {
+            Box::new(Expr::Bin(BinExpr {
+                op,
+                left: value,
+                right: Box::new(Expr::Lit(Lit::Num(Number {
+                    span: DUMMY_SP,
+                    value: 1.0,
+                    raw: None,
+                }))),
+                ..Default::default()
+            }))
+        };
+
+        let span = update.span;
+        let prefix = update.prefix;
+
+        // The object is both read and written.
+        return Some(once(setter.object(target.clone()), |obj| {
+            let current = Box::new(Expr::Unary(UnaryExpr {
+                span: DUMMY_SP,
+                op: UnaryOp::Plus,
+                arg: setter.read(&target, obj.clone()),
+            }));
+
+            if !used {
+                return self.set(span, setter, obj, step(current));
+            }
+
+            let value = ident("__value__");
+
+            let (argument, stored) = if prefix {
+                (step(current), value.clone())
+            } else {
+                (current, step(value.clone()))
+            };
+
+            return Expr::Await(AwaitExpr {
+                span,
+                arg: Box::new(Expr::Call(CallExpr {
+                    span,
+                    callee: Callee::Expr(Box::new(Expr::Paren(ParenExpr {
+                        span: DUMMY_SP,
+                        expr: Box::new(Expr::Arrow(ArrowExpr {
+                            params: vec![parameter("__value__")],
+                            body: Box::new(BlockStmtOrExpr::Expr(Box::new(Expr::Paren(
+                                ParenExpr {
+                                    span: DUMMY_SP,
+                                    expr: Box::new(Expr::Seq(SeqExpr {
+                                        span: DUMMY_SP,
+                                        exprs: vec![
+                                            Box::new(self.set(span, setter, obj, stored)),
+                                            value,
+                                        ],
+                                    })),
+                                },
+                            )))),
+                            is_async: true,
+                            ..Default::default()
+                        })),
+                    }))),
+                    args: vec![ExprOrSpread {
+                        spread: None,
+                        expr: argument,
+                    }],
+                    type_args: None,
+                    ..Default::default()
+                })),
+            });
+        }));
+    }
+}
+
+impl VisitMut for JavaScript {
+    fn visit_mut_script(&mut self, node: &mut Script) {
+        self.top_level = top_level(&node.body);
+        node.visit_with(&mut self.methods);
+        node.visit_with(&mut self.generators);
+
+        let mut iterations = Iterations {
+            generators: &mut self.generators,
+            unsupported: vec![],
+        };
+
+        node.visit_with(&mut iterations);
+        self.unsupported.extend(iterations.unsupported);
+
+        self.scopes.push(vec![]);
+        node.visit_mut_children_with(self);
+        self.scopes.pop();
+        node.body.splice(0..0, self.declarations());
+        node.body.splice(0..0, callbacks::helpers(&self.callbacks));
+
+        if self.loop_state {
+            node.body.insert(0, self.loop_state());
+        }
+
+        if self.watchdog.is_some() {
+            node.body.insert(0, declare([("__depth__", number(0.0))]));
+        }
 
-pub fn javascript() -> impl VisitMut {
-	return JavaScript::default();
-}
+        if self.debug {
+            node.body.insert(0, frame(null()));
+        }
+    }
 
-#[derive(Default)]
-pub struct JavaScript;
+    fn visit_mut_stmts(&mut self, node: &mut Vec<Stmt>) {
+        if !self.debug && !self.highlight_blocks && !self.coverage {
+            node.visit_mut_children_with(self);
+            return;
+        }
 
-impl JavaScript {
-    fn capitalize_first(&mut self, s: Atom) -> String {
-        s.chars()
-            .take(1)
-            .flat_map(|f| f.to_uppercase())
-            .chain(s.chars().skip(1))
-            .collect()
+        let mut stmts: Vec<Stmt> = Vec::with_capacity(node.len() * 2);
+
+        for mut stmt in std::mem::take(node) {
+            let span = stmt.span();
+            let block = self.block(&stmt);
+
+            if let (true, Some(id)) = (self.highlight_blocks, &block) {
+                stmts.push(self.running(span, id));
+            }
+
+            if self.coverage && instrumented(&stmt) {
+                let expr = self.cover(span, coverage::Kind::Statement, block);
+
+                stmts.push(Stmt::Expr(ExprStmt {
+                    span: DUMMY_SP,
+                    expr: Box::new(expr),
+                }));
+            }
+
+            let probe = self.probe(&stmt);
+            stmt.visit_mut_with(self);
+
+            stmts.extend(probe);
+            self.declare_locals(&stmt);
+            stmts.push(stmt);
+        }
+
+        *node = stmts;
     }
-}
 
-impl VisitMut for JavaScript {
-    fn visit_mut_stmt(&mut self, node: &mut Stmt) {
+    fn visit_mut_block_stmt(&mut self, node: &mut BlockStmt) {
+        self.scopes.push(vec![]);
         node.visit_mut_children_with(self);
+        self.scopes.pop();
+    }
 
-        match node {
-            Stmt::Decl(Decl::TsInterface(intr)) => {
-                let mut stmts: Vec<Stmt> = vec![];
-
-                for item in intr.body.body.iter() {
-                    if let TsTypeElement::TsPropertySignature(key) = item {
-                        let ts_types = match &key.type_ann {
-                            Some(t) => *t.type_ann.clone(),
-                            None => continue,
-                        };
-
-                        let name = match &*key.key {
-                            Expr::Ident(i) => i.sym.to_string(),
-                            Expr::Lit(Lit::Str(s)) => s.value.to_string(),
-                            _ => continue,
-                        };
-
-                        let mut args = vec![ExprOrSpread {
-                            spread: None,
-                            expr: Box::new(Expr::Lit(Lit::Str(Str {
-                                span: DUMMY_SP,
-                                value: name.into(),
-                                raw: None,
-                            }))),
-                        }];
-
-                        for ts_type in get_type(&ts_types) {
-                            args.push(ExprOrSpread {
-                                spread: None,
-                                expr: Box::new(Expr::Lit(Lit::Str(Str {
-                                    span: DUMMY_SP,
-                                    value: ts_type.into(),
-                                    raw: None,
-                                }))),
-                            });
-                        }
+    fn visit_mut_stmt(&mut self, node: &mut Stmt) {
+        let labeled = std::mem::take(&mut self.labeled);
 
-                        stmts.push(Stmt::Expr(ExprStmt {
-                            span: DUMMY_SP,
-                            expr: Box::new(Expr::Call(CallExpr {
-                                span: DUMMY_SP,
-                                callee: Callee::Expr(Box::new(Expr::Member(MemberExpr {
-                                    span: DUMMY_SP,
-                                    obj: Box::new(Expr::Ident(Ident {
-                                        sym: "self".into(),
-                                        optional: false,
-                                        ..Default::default()
-                                    })),
-                                    prop: MemberProp::Ident(IdentName {
-                                        span: DUMMY_SP,
-                                        sym: "declareVariable".into(),
-                                    }),
-                                }))),
-                                args,
-                                type_args: None,
-                                ..Default::default()
-                            })),
-                        }));
+        if let Stmt::Labeled(LabeledStmt { body, .. }) = node {
+            self.labeled = looping(body);
+        }
+
+        // A label has to stay on its loop, so it is timed and watched along.
+        let looped = !labeled && looping(node);
+
+        // Single statements get blocks, so that they can be probed or counted.
+        if self.debug || self.coverage {
+            match node {
+                Stmt::If(IfStmt { cons, alt, .. }) => {
+                    blockify(cons);
+
+                    if let Some(alt) = alt {
+                        if !matches!(**alt, Stmt::If(_)) {
+                            blockify(alt);
+                        }
                     }
                 }
+                Stmt::ForIn(ForInStmt { body, .. })
+                | Stmt::ForOf(ForOfStmt { body, .. })
+                | Stmt::For(ForStmt { body, .. })
+                | Stmt::While(WhileStmt { body, .. })
+                | Stmt::DoWhile(DoWhileStmt { body, .. }) => blockify(body),
+                _ => {}
+            }
+        }
 
-                *node = Stmt::Block(BlockStmt {
-                    span: DUMMY_SP,
-                    stmts,
-                    ..Default::default()
-                });
+        self.loops += looped as usize;
+        node.visit_mut_children_with(self);
+        self.loops -= looped as usize;
+
+        if self.coverage {
+            match node {
+                Stmt::If(IfStmt {
+                    span, cons, alt, ..
+                }) => {
+                    blockify(cons);
+
+                    if let Stmt::Block(block) = &mut **cons {
+                        self.branch(block.span, &mut block.stmts);
+                    }
+
+                    // The implicit `else` counts too, on the line of the `if`.
+                    let span = alt.as_ref().map_or(*span, |alt| alt.span());
+                    let alt =
+                        alt.get_or_insert_with(|| Box::new(Stmt::Block(BlockStmt::default())));
+
+                    blockify(alt);
+
+                    if let Stmt::Block(block) = &mut **alt {
+                        self.branch(span, &mut block.stmts);
+                    }
+                }
+                Stmt::Switch(switch) => {
+                    for case in switch.cases.iter_mut() {
+                        self.branch(case.span, &mut case.cons);
+                    }
+                }
+                _ => {}
             }
+        }
 
+        match node {
             Stmt::ForIn(ForInStmt { body, .. })
             | Stmt::ForOf(ForOfStmt { body, .. })
             | Stmt::For(ForStmt { body, .. })
             | Stmt::While(WhileStmt { body, .. })
             | Stmt::DoWhile(DoWhileStmt { body, .. }) => {
-                let mut stmts: Vec<Stmt> = match *body.clone() {
-                    Stmt::Block(block) => block.stmts.clone(),
-                    _ => vec![*body.clone()],
-                };
+                let mut prologue: Vec<Stmt> = self.pause().into_iter().collect();
+
+                if let Some(watchdog) = self.runaways() {
+                    prologue.push(self.runaway(watchdog));
+                }
 
-                stmts.insert(
-                    0,
-                    Stmt::Expr(ExprStmt {
+                if !prologue.is_empty() {
+                    let mut stmts: Vec<Stmt> = match (**body).take() {
+                        Stmt::Block(block) => block.stmts,
+                        stmt => vec![stmt],
+                    };
+
+                    stmts.splice(0..0, prologue);
+
+                    **body = Stmt::Block(BlockStmt {
                         span: DUMMY_SP,
-                        expr: Box::new(Expr::Await(AwaitExpr {
-                            span: DUMMY_SP,
-                            arg: Box::new(Expr::New(NewExpr {
-                                span: DUMMY_SP,
-                                callee: Box::new(Expr::Ident(Ident {
-                                    span: DUMMY_SP,
-                                    sym: "Promise".into(),
-                                    optional: false,
-                                    ..Default::default()
-                                })),
-                                args: Some(vec![ExprOrSpread {
-                                    spread: None,
-                                    expr: Box::new(Expr::Member(MemberExpr {
-                                        span: DUMMY_SP,
-                                        obj: Box::new(Expr::Ident(Ident {
-                                            span: DUMMY_SP,
-                                            sym: "self".into(),
-                                            ..Default::default()
-                                        })),
-                                        prop: MemberProp::Ident(IdentName {
-                                            sym: "delay".into(),
-                                            ..Default::default()
-                                        }),
-                                    })),
-                                }]),
-                                type_args: None,
-                                ..Default::default()
-                            })),
-                        })),
-                    }),
-                );
+                        stmts,
+                        ..Default::default()
+                    });
+                }
+            }
 
-                *body = Box::new(Stmt::Block(BlockStmt {
-                    span: DUMMY_SP,
-                    stmts,
-                    ..Default::default()
-                }));
+            _ => {}
+        }
+
+        // Every loop counts its own iterations, from zero when it starts.
+        if let (true, Some(_)) = (looped, self.runaways()) {
+            *node = Stmt::Block(BlockStmt {
+                span: node.span(),
+                stmts: vec![declare([("__steps__", number(0.0))]), node.take()],
+                ..Default::default()
+            });
+        }
+
+        if self.profile && !self.generator && looped {
+            let span = node.span();
+            let name = self.functions.last().cloned().flatten();
+            let stmts = self.time(span, Kind::Loop, name, vec![node.take()]);
+
+            *node = Stmt::Block(BlockStmt {
+                stmts,
+                ..Default::default()
+            });
+        }
+    }
+
+    fn visit_mut_expr_stmt(&mut self, node: &mut ExprStmt) {
+        if let Expr::Update(update) = &mut *node.expr {
+            update.arg.visit_mut_with(self);
+
+            if let Some(expr) = self.update(update, false) {
+                *node.expr = expr;
+            }
+        } else {
+            node.expr.visit_mut_with(self);
+        }
+    }
+
+    fn visit_mut_call_expr(&mut self, node: &mut CallExpr) {
+        let event = match &node.callee {
+            Callee::Expr(callee) => match &**callee {
+                Expr::Member(member) if matches!(&*member.obj, Expr::Ident(id) if id.sym == "self") => {
+                    get_property(member).filter(|name| name.starts_with("when"))
+                }
+                _ => None,
+            },
+            _ => None,
+        };
+
+        // Array callbacks in code that can't be async stay synchronous too.
+        let callback = self.sync && self.array_method(node).is_some();
+
+        node.callee.visit_mut_with(self);
+
+        for arg in node.args.iter_mut() {
+            if let Expr::Arrow(_) | Expr::Fn(_) = &*arg.expr {
+                self.handler = event.as_ref().map(|event| event.to_string());
+                self.sync_function = callback;
             }
 
-            _ => return,
+            arg.visit_mut_with(self);
         }
+
+        self.callback(node);
+    }
+
+    fn visit_mut_callee(&mut self, node: &mut Callee) {
+        // Calls of top-level functions pass `self` as an argument instead.
+        if let Callee::Expr(callee) = node {
+            if let Expr::Ident(_) = &**callee {
+                return;
+            }
+        }
+
+        node.visit_mut_children_with(self);
     }
 
     fn visit_mut_fn_decl(&mut self, node: &mut FnDecl) {
+        let top = self.top_level.contains(&node.ident.to_id());
+
         node.function.is_async = true;
+        self.name = Some(node.ident.sym.to_string());
+        self.top = top;
         node.visit_mut_children_with(self);
+
+        if top {
+            self.receive(&mut node.function);
+        }
     }
 
-    fn visit_mut_expr(&mut self, node: &mut Expr) {
+    fn visit_mut_class_method(&mut self, node: &mut ClassMethod) {
+        self.name = key(&node.key);
+        self.sync_function = node.kind != MethodKind::Method;
         node.visit_mut_children_with(self);
 
-        match node {
-            Expr::Call(call) => {
-                if let Callee::Expr(expression) = call.callee.clone() {
-                    if let Expr::Ident(id) = *expression {
-                        if id.sym != "String" && id.sym != "Number" {
-                            call.args.insert(
-                                0,
-                                ExprOrSpread {
-                                    spread: None,
-                                    expr: Box::new(Expr::Ident(Ident {
-                                        span: DUMMY_SP,
-                                        sym: "self".into(),
-                                        optional: false,
-                                        ..Default::default()
-                                    })),
-                                },
-                            );
-                        }
-                    }
+        match node.kind {
+            MethodKind::Method => node.function.is_async = true,
+            MethodKind::Getter => self.synchronous(&node.function.body, "A getter"),
+            MethodKind::Setter => self.synchronous(&node.function.body, "A setter"),
+        }
+
+        iterator(&mut node.key, &node.function);
+    }
+
+    fn visit_mut_private_method(&mut self, node: &mut PrivateMethod) {
+        self.name = Some(node.key.name.to_string());
+        self.sync_function = node.kind != MethodKind::Method;
+        node.visit_mut_children_with(self);
+
+        match node.kind {
+            MethodKind::Method => node.function.is_async = true,
+            MethodKind::Getter => self.synchronous(&node.function.body, "A getter"),
+            MethodKind::Setter => self.synchronous(&node.function.body, "A setter"),
+        }
+    }
+
+    fn visit_mut_constructor(&mut self, node: &mut Constructor) {
+        self.visit_sync(node);
+        self.synchronous(&node.body, "A constructor");
+    }
+
+    fn visit_mut_class_prop(&mut self, node: &mut ClassProp) {
+        self.visit_sync(node);
+        self.synchronous(&node.value, "A class field");
+    }
+
+    fn visit_mut_static_block(&mut self, node: &mut StaticBlock) {
+        self.visit_sync(node);
+        self.synchronous(&node.body, "A static block");
+    }
+
+    // Namespaces get lowered into plain functions.
+    fn visit_mut_ts_module_decl(&mut self, node: &mut TsModuleDecl) {
+        self.visit_sync(node);
+        self.synchronous(&node.body, "A namespace body");
+    }
+
+    fn visit_mut_method_prop(&mut self, node: &mut MethodProp) {
+        self.name = key(&node.key);
+        node.visit_mut_children_with(self);
+        node.function.is_async = true;
+        iterator(&mut node.key, &node.function);
+    }
+
+    fn visit_mut_getter_prop(&mut self, node: &mut GetterProp) {
+        self.visit_sync(node);
+        self.synchronous(&node.body, "A getter");
+    }
+
+    fn visit_mut_setter_prop(&mut self, node: &mut SetterProp) {
+        self.visit_sync(node);
+        self.synchronous(&node.body, "A setter");
+    }
+
+    fn visit_mut_fn_expr(&mut self, node: &mut FnExpr) {
+        if let Some(ident) = &node.ident {
+            self.name = Some(ident.sym.to_string());
+        }
+
+        node.visit_mut_children_with(self);
+    }
+
+    fn visit_mut_var_declarator(&mut self, node: &mut VarDeclarator) {
+        if let (Pat::Ident(binding), Some(Expr::Fn(_) | Expr::Arrow(_))) =
+            (&node.name, node.init.as_deref())
+        {
+            self.name = Some(binding.id.sym.to_string());
+        }
+
+        let top_level = match &node.name {
+            Pat::Ident(binding) => self.top_level.contains(&binding.id.to_id()),
+            _ => false,
+        };
+
+        self.top = top_level;
+        node.visit_mut_children_with(self);
+
+        match node.init.as_deref_mut() {
+            Some(Expr::Fn(fn_expr)) if top_level => self.receive(&mut fn_expr.function),
+            Some(Expr::Arrow(arrow)) if top_level => {
+                if self.debug {
+                    let body = block_body(&mut arrow.body);
+                    body.stmts.insert(0, frame(ident("__caller__")));
                 }
 
-                *node = Expr::Await(AwaitExpr {
-                    span: DUMMY_SP,
-                    arg: Box::new(Expr::Call(call.clone())),
-                });
+                arrow
+                    .params
+                    .splice(0..0, self.parameters().into_iter().map(parameter));
             }
+            _ => {}
+        }
+    }
 
-            Expr::Assign(assign) => {
-                let left = match &assign.left {
-                    AssignTarget::Simple(SimpleAssignTarget::Member(t)) => t,
-                    _ => return,
-                };
+    fn visit_mut_for_stmt(&mut self, node: &mut ForStmt) {
+        let mut scope = vec![];
 
-                if let Expr::Member(member) = *left.obj.clone() {
-                    let argument = match assign.op {
-                        AssignOp::Assign => assign.right.clone(),
-                        AssignOp::AddAssign => Box::new(Expr::Bin(BinExpr {
-                            op: BinaryOp::Add,
-                            left: Box::new(Expr::Member(member.clone())),
-                            right: assign.right.clone(),
-                            ..Default::default()
-                        })),
-                        AssignOp::SubAssign => Box::new(Expr::Bin(BinExpr {
-                            op: BinaryOp::Sub,
-                            left: Box::new(Expr::Member(member.clone())),
-                            right: assign.right.clone(),
-                            ..Default::default()
-                        })),
-                        AssignOp::MulAssign => Box::new(Expr::Bin(BinExpr {
-                            op: BinaryOp::Mul,
-                            left: Box::new(Expr::Member(member.clone())),
-                            right: assign.right.clone(),
-                            ..Default::default()
-                        })),
-                        AssignOp::DivAssign => Box::new(Expr::Bin(BinExpr {
-                            op: BinaryOp::Div,
-                            left: Box::new(Expr::Member(member.clone())),
-                            right: assign.right.clone(),
-                            ..Default::default()
-                        })),
-                        AssignOp::ModAssign => Box::new(Expr::Bin(BinExpr {
-                            op: BinaryOp::Mod,
-                            left: Box::new(Expr::Member(member.clone())),
-                            right: assign.right.clone(),
-                            ..Default::default()
-                        })),
-                        AssignOp::AndAssign => Box::new(Expr::Bin(BinExpr {
-                            op: BinaryOp::LogicalAnd,
-                            left: Box::new(Expr::Member(member.clone())),
-                            right: assign.right.clone(),
-                            ..Default::default()
-                        })),
-                        AssignOp::OrAssign => Box::new(Expr::Bin(BinExpr {
-                            op: BinaryOp::LogicalOr,
-                            left: Box::new(Expr::Member(member.clone())),
-                            right: assign.right.clone(),
-                            ..Default::default()
-                        })),
-                        AssignOp::BitAndAssign => Box::new(Expr::Bin(BinExpr {
-                            op: BinaryOp::BitAnd,
-                            left: Box::new(Expr::Member(member.clone())),
-                            right: assign.right.clone(),
-                            ..Default::default()
-                        })),
-                        AssignOp::BitOrAssign => Box::new(Expr::Bin(BinExpr {
-                            op: BinaryOp::BitOr,
-                            left: Box::new(Expr::Member(member.clone())),
-                            right: assign.right.clone(),
-                            ..Default::default()
-                        })),
-                        AssignOp::BitXorAssign => Box::new(Expr::Bin(BinExpr {
-                            op: BinaryOp::BitXor,
-                            left: Box::new(Expr::Member(member.clone())),
-                            right: assign.right.clone(),
-                            ..Default::default()
-                        })),
-                        AssignOp::LShiftAssign => Box::new(Expr::Bin(BinExpr {
-                            op: BinaryOp::LShift,
-                            left: Box::new(Expr::Member(member.clone())),
-                            right: assign.right.clone(),
-                            ..Default::default()
-                        })),
-                        AssignOp::RShiftAssign => Box::new(Expr::Bin(BinExpr {
-                            op: BinaryOp::RShift,
-                            left: Box::new(Expr::Member(member.clone())),
-                            right: assign.right.clone(),
-                            ..Default::default()
-                        })),
-                        AssignOp::ZeroFillRShiftAssign => Box::new(Expr::Bin(BinExpr {
-                            op: BinaryOp::ZeroFillRShift,
-                            left: Box::new(Expr::Member(member.clone())),
-                            right: assign.right.clone(),
-                            ..Default::default()
-                        })),
-                        AssignOp::ExpAssign => Box::new(Expr::Bin(BinExpr {
-                            op: BinaryOp::Exp,
-                            left: Box::new(Expr::Member(member.clone())),
-                            right: assign.right.clone(),
-                            ..Default::default()
-                        })),
-                        AssignOp::NullishAssign => Box::new(Expr::Bin(BinExpr {
-                            op: BinaryOp::NullishCoalescing,
-                            left: Box::new(Expr::Member(member.clone())),
-                            right: assign.right.clone(),
-                            ..Default::default()
-                        })),
-                    };
+        if let Some(VarDeclOrExpr::VarDecl(var)) = &node.init {
+            for decl in var.decls.iter() {
+                bindings(&decl.name, &mut scope);
+            }
+        }
+
+        self.scopes.push(scope);
+        node.visit_mut_children_with(self);
+        self.scopes.pop();
+    }
+
+    fn visit_mut_for_in_stmt(&mut self, node: &mut ForInStmt) {
+        self.scopes.push(head(&node.left));
+        node.visit_mut_children_with(self);
+        self.scopes.pop();
+    }
+
+    fn visit_mut_for_of_stmt(&mut self, node: &mut ForOfStmt) {
+        self.scopes.push(head(&node.left));
+        node.visit_mut_children_with(self);
+        self.scopes.pop();
+
+        if self.generators.iterates(&node.right) {
+            node.is_await = true;
+        }
+    }
+
+    fn visit_mut_function(&mut self, node: &mut Function) {
+        let mut scope = vec![];
+
+        for param in node.params.iter() {
+            bindings(&param.pat, &mut scope);
+        }
+
+        let handler = self.handler.take();
+        let top = std::mem::take(&mut self.top);
+
+        let generator = std::mem::replace(&mut self.generator, node.is_generator);
+        let sync = std::mem::replace(&mut self.sync, std::mem::take(&mut self.sync_function));
+        let loops = std::mem::take(&mut self.loops);
+        let loop_state = std::mem::take(&mut self.loop_state);
+
+        self.functions.push(self.name.take());
+        self.scopes.push(scope);
+        node.visit_mut_children_with(self);
+        self.scopes.pop();
+
+        // Every run of the function counts its own iterations.
+        if let (true, Some(body)) = (self.loop_state, &mut node.body) {
+            body.stmts.insert(0, self.loop_state());
+        }
+
+        self.generator = generator;
+        self.sync = sync;
+        self.loops = loops;
+        self.loop_state = loop_state;
+
+        // A generator is suspended at every `yield`, so that
+        // neither its depth nor its time would be its own.
+        if node.is_generator {
+            self.functions.pop();
+            return;
+        }
+
+        if let (Some(watchdog), Some(body)) = (self.watchdog, &mut node.body) {
+            self.watch(watchdog, body, handler.is_some(), top);
+        }
+
+        let root = self.debug && handler.is_some();
+
+        if let (true, Some(body)) = (self.profile, &mut node.body) {
+            let (kind, name) = self.site(handler);
+            body.stmts = self.time(node.span, kind, name, std::mem::take(&mut body.stmts));
+        }
+
+        // Every run of a handler is a script of its own for the debugger.
+        if let (true, Some(body)) = (root, &mut node.body) {
+            body.stmts.insert(0, frame(null()));
+        }
+
+        self.functions.pop();
+    }
+
+    fn visit_mut_arrow_expr(&mut self, node: &mut ArrowExpr) {
+        let mut scope = vec![];
+
+        for param in node.params.iter() {
+            bindings(param, &mut scope);
+        }
+
+        let handler = self.handler.take();
+        let top = std::mem::take(&mut self.top);
+
+        let generator = std::mem::take(&mut self.generator);
+        let sync = std::mem::replace(&mut self.sync, std::mem::take(&mut self.sync_function));
+        let loops = std::mem::take(&mut self.loops);
+        let loop_state = std::mem::take(&mut self.loop_state);
+
+        self.functions.push(self.name.take());
+        self.scopes.push(scope);
+        node.visit_mut_children_with(self);
+        self.scopes.pop();
+
+        // Every run of the function counts its own iterations.
+        if self.loop_state {
+            let body = block_body(&mut node.body);
+            body.stmts.insert(0, self.loop_state());
+        }
+
+        self.generator = generator;
+        self.sync = sync;
+        self.loops = loops;
+        self.loop_state = loop_state;
+
+        let root = self.debug && handler.is_some();
+
+        if self.watchdog.is_some() || self.profile || root {
+            let body = block_body(&mut node.body);
+
+            if let Some(watchdog) = self.watchdog {
+                self.watch(watchdog, body, handler.is_some(), top);
+            }
+
+            if self.profile {
+                let (kind, name) = self.site(handler);
+                body.stmts = self.time(node.span, kind, name, std::mem::take(&mut body.stmts));
+            }
+
+            // Every run of a handler is a script of its own for the debugger.
+            if root {
+                body.stmts.insert(0, frame(null()));
+            }
+        }
+
+        self.functions.pop();
+    }
+
+    fn visit_mut_expr(&mut self, node: &mut Expr) {
+        node.visit_mut_children_with(self);
 
-                    if let Expr::Member(member) = *member.obj {
-                        if is_property(&member, "variables") {
-                            *node = Expr::Await(AwaitExpr {
-                                span: DUMMY_SP,
-                                arg: Box::new(Expr::Call(CallExpr {
-                                    span: DUMMY_SP,
-                                    callee: Callee::Expr(Box::new(Expr::Member(MemberExpr {
-                                        span: DUMMY_SP,
-                                        obj: member.obj.clone(),
-                                        prop: MemberProp::Ident(IdentName {
-                                            sym: "setVariable".into(),
-                                            ..Default::default()
-                                        }),
-                                    }))),
-                                    args: vec![
-                                        ExprOrSpread {
-                                            spread: None,
-                                            expr: Box::new(Expr::Lit(Lit::Str(Str {
-                                                span: DUMMY_SP,
-                                                value: match get_property(&member) {
-                                                    Some(atom) => atom,
-                                                    None => return,
-                                                },
-                                                raw: None,
-                                            }))),
-                                        },
-                                        ExprOrSpread {
-                                            spread: None,
-                                            expr: argument,
-                                        },
-                                    ],
-                                    type_args: None,
-                                    ..Default::default()
-                                })),
-                            });
-                        } else if is_property(&member, "effects") {
-                            *node = Expr::Await(AwaitExpr {
-                                span: DUMMY_SP,
-                                arg: Box::new(Expr::Call(CallExpr {
-                                    span: DUMMY_SP,
-                                    callee: Callee::Expr(Box::new(Expr::Member(MemberExpr {
-                                        span: DUMMY_SP,
-                                        obj: member.obj.clone(),
-                                        prop: MemberProp::Ident(IdentName {
-                                            sym: "setEffect".into(),
-                                            ..Default::default()
-                                        }),
-                                    }))),
-                                    args: vec![
-                                        ExprOrSpread {
-                                            spread: None,
-                                            expr: Box::new(Expr::Lit(Lit::Str(Str {
-                                                span: DUMMY_SP,
-                                                value: match get_property(&member) {
-                                                    Some(atom) => atom,
-                                                    None => return,
-                                                },
-                                                raw: None,
-                                            }))),
-                                        },
-                                        ExprOrSpread {
-                                            spread: None,
-                                            expr: argument,
-                                        },
-                                    ],
-                                    type_args: None,
-                                    ..Default::default()
-                                })),
-                            });
+        match node {
+            Expr::Call(call) => {
+                if let Callee::Expr(callee) = &call.callee {
+                    if let Expr::Ident(id) = &**callee {
+                        if self.top_level.contains(&id.to_id()) {
+                            call.args.splice(0..0, self.receivers());
                         }
-                    } else {
-                        let value = match get_property(&left) {
-                            Some(atom) => atom,
-                            None => return,
-                        };
-
-                        match get_property(&left) {
-                            Some(atom) => {
-                                if atom == "x"
-                                    || atom == "y"
-                                    || atom == "draggable"
-                                    || atom == "volume"
-                                {
-                                    *node = Expr::Await(AwaitExpr {
-                                        span: DUMMY_SP,
-                                        arg: Box::new(Expr::Call(CallExpr {
-                                            span: DUMMY_SP,
-                                            callee: Callee::Expr(Box::new(Expr::Member(
-                                                MemberExpr {
-                                                    span: DUMMY_SP,
-                                                    obj: left.obj.clone(),
-                                                    prop: MemberProp::Ident(IdentName {
-                                                        sym: format!(
-                                                            "set{}",
-                                                            self.capitalize_first(atom)
-                                                        )
-                                                        .into(),
-                                                        ..Default::default()
-                                                    }),
-                                                },
-                                            ))),
-                                            args: vec![
-                                                ExprOrSpread {
-                                                    spread: None,
-                                                    expr: Box::new(Expr::Lit(Lit::Str(Str {
-                                                        span: DUMMY_SP,
-                                                        value,
-                                                        raw: None,
-                                                    }))),
-                                                },
-                                                ExprOrSpread {
-                                                    spread: None,
-                                                    expr: argument,
-                                                },
-                                            ],
-                                            type_args: None,
-                                            ..Default::default()
-                                        })),
-                                    });
-                                } else if atom == "direction" {
-                                    *node = Expr::Await(AwaitExpr {
-                                        span: DUMMY_SP,
-                                        arg: Box::new(Expr::Call(CallExpr {
-                                            span: DUMMY_SP,
-                                            callee: Callee::Expr(Box::new(Expr::Member(
-                                                MemberExpr {
-                                                    span: DUMMY_SP,
-                                                    obj: left.obj.clone(),
-                                                    prop: MemberProp::Ident(IdentName {
-                                                        sym: "pointInDirection".into(),
-                                                        ..Default::default()
-                                                    }),
-                                                },
-                                            ))),
-                                            args: vec![
-                                                ExprOrSpread {
-                                                    spread: None,
-                                                    expr: Box::new(Expr::Lit(Lit::Str(Str {
-                                                        span: DUMMY_SP,
-                                                        value,
-                                                        raw: None,
-                                                    }))),
-                                                },
-                                                ExprOrSpread {
-                                                    spread: None,
-                                                    expr: argument,
-                                                },
-                                            ],
-                                            type_args: None,
-                                            ..Default::default()
-                                        })),
-                                    });
-                                }
+                    }
+                }
+
+                let pauses = self.loops > 0
+                    && self.runaways().is_some()
+                    && match &call.callee {
+                        Callee::Expr(callee) => match &**callee {
+                            Expr::Member(member) => {
+                                get_property(member).is_some_and(|name| methods::pauses(&name))
                             }
+                            _ => false,
+                        },
+                        _ => false,
+                    };
 
+                if self.awaited(call) {
+                    *node = Expr::Await(AwaitExpr {
+                        span: call.span,
+                        arg: Box::new(node.take()),
+                    });
+                }
+
+                // A wait of the engine is a pause too: `(__steps__ = 0, await self.wait(1))`
+                if pauses {
+                    *node = Expr::Paren(ParenExpr {
+                        span: DUMMY_SP,
+                        expr: Box::new(Expr::Seq(SeqExpr {
+                            span: DUMMY_SP,
+                            exprs: vec![
+                                assignment("__steps__", number(0.0)),
+                                Box::new(node.take()),
+                            ],
+                        })),
+                    });
+                }
+            }
+
+            Expr::Assign(assign) => {
+                let setter = match &assign.left {
+                    AssignTarget::Simple(SimpleAssignTarget::Member(target)) => {
+                        match self.setter(target) {
+                            Some(setter) => setter,
                             None => return,
                         }
                     }
-                }
-            }
+                    _ => return,
+                };
+
+                let AssignExpr {
+                    span,
+                    op,
+                    left,
+                    right,
+                    ..
+                } = std::mem::take(assign);
+
+                let target = match left {
+                    AssignTarget::Simple(SimpleAssignTarget::Member(target)) => target,
+                    _ => return,
+                };
+
+                *node = match op.to_update() {
+                    // The object is both read and written.
+                    Some(op) => once(setter.object(target.clone()), |obj| {
+                        let value = Box::new(Expr::Bin(BinExpr {
+                            op,
+                            left: setter.read(&target, obj.clone()),
+                            right,
+                            ..Default::default()
+                        }));
 
-            Expr::Member(node) => {
-                if let Expr::Member(member) = *node.obj.clone() {
-                    if let MemberProp::Ident(IdentName { sym: id, .. }) = member.prop {
-                        if id == "variables" {}
+                        return self.set(span, setter, obj, value);
+                    }),
+                    None => {
+                        let obj = setter.object(target);
+                        self.set(span, setter, obj, right)
                     }
+                };
+            }
+
+            Expr::Update(update) => {
+                if let Some(expr) = self.update(update, true) {
+                    *node = expr;
                 }
             }
 
+            Expr::Cond(cond) if self.coverage => {
+                cond.cons = self.covered(cond.cons.take());
+                cond.alt = self.covered(cond.alt.take());
+            }
+
+            Expr::Bin(bin)
+                if self.coverage
+                    && matches!(
+                        bin.op,
+                        BinaryOp::LogicalAnd | BinaryOp::LogicalOr | BinaryOp::NullishCoalescing
+                    ) =>
+            {
+                bin.right = self.covered(bin.right.take());
+            }
+
+            // Top-level functions referenced as values get `self` bound.
+            Expr::Ident(id) if self.top_level.contains(&id.to_id()) => {
+                let mut args = vec![ExprOrSpread {
+                    spread: None,
+                    expr: null(),
+                }];
+                args.extend(self.receivers());
+
+                *node = Expr::Call(CallExpr {
+                    span: id.span,
+                    callee: Callee::Expr(member(Box::new(node.take()), "bind")),
+                    args,
+                    ..Default::default()
+                });
+            }
+
             Expr::Arrow(arrow) => {
                 arrow.is_async = true;
             }
@@ -473,7 +2165,15 @@ impl VisitMut for JavaScript {
     }
 
     fn visit_mut_catch_clause(&mut self, node: &mut CatchClause) {
+        let mut scope = vec![];
+
+        if let Some(param) = &node.param {
+            bindings(param, &mut scope);
+        }
+
+        self.scopes.push(scope);
         node.body.visit_mut_with(self);
+        self.scopes.pop();
 
         let param = match &node.param {
             Some(Pat::Ident(id)) => id.id.sym.clone(),
@@ -488,7 +2188,10 @@ impl VisitMut for JavaScript {
 
                 "__error__".into()
             }
-            _ => return,
+            Some(pat) => {
+                self.unsupported(pat.span(), "Only a plain identifier can be caught");
+                return;
+            }
         };
 
         node.body.stmts.insert(
@@ -528,55 +2231,622 @@ impl VisitMut for JavaScript {
             }),
         );
     }
+}
 
-    fn visit_mut_for_stmt(&mut self, node: &mut ForStmt) {
-        node.visit_mut_children_with(self);
+#[cfg(test)]
+mod tests {
+    use swc::Compiler;
+    use swc_core::common::{sync::Lrc, FilePathMapping, SourceMap};
+
+    use crate::coverage;
+    use crate::diagnostics::{CompileError, Severity};
+    use crate::options::TransformOptions;
+    use crate::profiler;
+    use crate::swc_utils::{parse, transform, Transformed};
+
+    fn compile(code: &str, options: &str) -> Result<Transformed, CompileError> {
+        let compiler = Compiler::new(Lrc::new(SourceMap::new(FilePathMapping::empty())));
+        let options: TransformOptions = serde_json::from_str(options).unwrap();
+        return transform(&compiler, code.to_string(), options);
+    }
 
-        let mut stmts: Vec<Stmt> = match *node.body.clone() {
-            Stmt::Block(block) => block.stmts.clone(),
-            _ => vec![*node.body.clone()],
+    /// Compiled code without whitespace, so that it doesn't depend on the formatting
+    fn output(code: &str, options: &str) -> String {
+        return match compile(code, options) {
+            Ok(transformed) => transformed.code.split_whitespace().collect(),
+            Err(error) => panic!("{:?}", error),
         };
+    }
 
-        stmts.insert(
-            0,
-            Stmt::Expr(ExprStmt {
-                span: DUMMY_SP,
-                expr: Box::new(Expr::Await(AwaitExpr {
-                    span: DUMMY_SP,
-                    arg: Box::new(Expr::New(NewExpr {
-                        span: DUMMY_SP,
-                        callee: Box::new(Expr::Ident(Ident {
-                            span: DUMMY_SP,
-                            sym: "Promise".into(),
-                            optional: false,
-                            ..Default::default()
-                        })),
-                        args: Some(vec![ExprOrSpread {
-                            spread: None,
-                            expr: Box::new(Expr::Member(MemberExpr {
-                                span: DUMMY_SP,
-                                obj: Box::new(Expr::Ident(Ident {
-                                    span: DUMMY_SP,
-                                    sym: "self".into(),
-                                    ..Default::default()
-                                })),
-                                prop: MemberProp::Ident(IdentName {
-                                    sym: "delay".into(),
-                                    ..Default::default()
-                                }),
-                            })),
-                        }]),
-                        type_args: None,
-                        ..Default::default()
-                    })),
-                })),
-            }),
+    #[test]
+    fn probes_stay_synchronous_in_constructors_and_accessors() {
+        let code = output(
+            "class A { constructor() { this.x = 1; } get y() { return 2; } set y(v) { this.x = v; } m() { return 3; } }",
+            r#"{"debug": true}"#,
         );
 
-        node.body = Box::new(Stmt::Block(BlockStmt {
-            span: DUMMY_SP,
-            stmts,
-            ..Default::default()
-        }));
+        assert!(code.contains("constructor(){self.__probe(1,"));
+        assert!(code.contains("gety(){self.__probe(2,"));
+        assert!(code.contains("sety(v){self.__probe(3,"));
+        assert!(code.contains("asyncm(){awaitself.__probe(4,"));
+    }
+
+    #[test]
+    fn probes_stay_synchronous_in_callbacks_of_synchronous_code() {
+        let code = output(
+            "class A { constructor() { this.x = [1].map(v => { return v * 2; }); } }",
+            r#"{"debug": true}"#,
+        );
+
+        assert!(code.contains(".map((v)=>{self.__probe("));
+        assert!(!code.contains("__map__"));
+    }
+
+    #[test]
+    fn calls_to_methods_of_the_script_are_awaited() {
+        let code = output(
+            "const enemy = { move(n: number) { self.wait(1); } }; enemy.move(10); const list = [1]; list.push(2);",
+            "{}",
+        );
+
+        assert!(code.contains("asyncmove(n){awaitself.wait(1);}"));
+        assert!(code.contains("awaitenemy.move(10);"));
+        assert!(code.contains("list.push(2);"));
+        assert!(!code.contains("awaitlist.push"));
+    }
+
+    #[test]
+    fn iterators_of_generators_are_iterated_with_for_await() {
+        let code = output(
+            "function* gen() { yield 1; } const it = gen(); for (const x of it) { self.say(x); } for (const y of gen()) {} const r = it.next();",
+            "{}",
+        );
+
+        assert!(code.contains("asyncfunction*gen(self)"));
+        assert!(code.contains("forawait(constxofit)"));
+        assert!(code.contains("forawait(constyofgen(self))"));
+        assert!(code.contains("constr=awaitit.next();"));
+    }
+
+    #[test]
+    fn other_uses_of_generators_are_unsupported() {
+        for code in [
+            "function* gen() { yield 1; } const a = [...gen()];",
+            "function* gen() { yield 1; } const a = Array.from(gen());",
+            "function* gen() { yield 1; } const [a, b] = gen();",
+            "function* gen() { yield 1; } const it = gen(); const a = [...it];",
+        ] {
+            assert!(
+                matches!(compile(code, "{}"), Err(CompileError::Unsupported(_))),
+                "{}",
+                code
+            );
+        }
+    }
+
+    #[test]
+    fn every_loop_counts_its_own_iterations() {
+        let code = output(
+            "for (let i = 0; i < 3; i++) {} while (true) { self.move(1); self.wait(0.1); }",
+            r#"{"watchdog": {}, "loopYield": {"strategy": "never"}}"#,
+        );
+
+        assert!(code.starts_with("let__depth__=0;"));
+        assert_eq!(code.matches("{let__steps__=0;").count(), 2);
+        assert!(code.contains("__steps__=0,awaitself.wait(0.1);"));
+    }
+
+    #[test]
+    fn labeled_loops_keep_their_label() {
+        let code = output(
+            "outer: for (let i = 0; i < 3; i++) { for (const x of [1]) { continue outer; } }",
+            r#"{"watchdog": {}, "loopYield": {"strategy": "budget", "milliseconds": 16}}"#,
+        );
+
+        assert!(code.contains("{let__steps__=0;outer:for("));
+        assert!(code.contains("__frame__=performance.now();"));
+    }
+
+    #[test]
+    fn loops_pausing_in_every_iteration_are_not_counted() {
+        let code = output("while (true) { self.wait(1); }", r#"{"watchdog": {}}"#);
+
+        assert!(!code.contains("__steps__"));
+        assert!(code.contains("awaitnewPromise(self.delay);"));
+    }
+
+    #[test]
+    fn updates_of_engine_state_call_the_setters() {
+        let code = output(
+            "interface Variables { score: number; } self.variables.score++; --self.effects.ghost; self.x++;",
+            "{}",
+        );
+
+        assert!(code.contains(r#"awaitself.setVariable("score",+self.variables.score+1);"#));
+        assert!(code.contains(r#"awaitself.setEffect("ghost",+self.effects.ghost-1);"#));
+        assert!(code.contains(r#"awaitself.setX("x",+self.x+1);"#));
+    }
+
+    #[test]
+    fn updates_keep_their_value() {
+        let code = output(
+            "interface Variables { score: number; } const a = self.variables.score++; const b = ++self.effects.ghost;",
+            "{}",
+        );
+
+        // Postfix updates give the old value, prefix ones the new value.
+        assert!(code.contains(
+            r#"consta=await(async(__value__)=>(awaitself.setVariable("score",__value__+1),__value__))(+self.variables.score);"#
+        ));
+        assert!(code.contains(
+            r#"constb=await(async(__value__)=>(awaitself.setEffect("ghost",__value__),__value__))(+self.effects.ghost+1);"#
+        ));
+    }
+
+    #[test]
+    fn objects_of_updates_are_evaluated_once() {
+        let code = output(
+            r#"function next() { return "Enemy"; } $[next()].x++; const y = $[next()].y--; $[next()].x += 2;"#,
+            "{}",
+        );
+
+        assert_eq!(code.matches("awaitnext(self)").count(), 3);
+        assert!(code.contains(
+            r#"await(async(__object__)=>await__object__.setX("x",+__object__.x+1))($[awaitnext(self)]);"#
+        ));
+        assert!(code.contains(
+            r#"consty=await(async(__object__)=>await(async(__value__)=>(await__object__.setY("y",__value__-1),__value__))(+__object__.y))($[awaitnext(self)]);"#
+        ));
+        assert!(code.contains(
+            r#"await(async(__object__)=>await__object__.setX("x",__object__.x+2))($[awaitnext(self)]);"#
+        ));
+    }
+
+    #[test]
+    fn updates_of_local_variables_stay() {
+        let code = output("let n = 0; n++; const m = --n;", "{}");
+        assert_eq!(code, "letn=0;n++;constm=--n;");
+    }
+
+    #[test]
+    fn every_kind_of_loop_waits_once_per_iteration() {
+        let code = output(
+            "for (let i = 0; i < 3; i++) {} for (const k in {}) {} for (const x of []) {} do {} while (false); while (false) {}",
+            "{}",
+        );
+
+        assert_eq!(code.matches("awaitnewPromise(self.delay);").count(), 5);
+    }
+
+    #[test]
+    fn loops_wait_every_few_iterations() {
+        let code = output(
+            "for (let i = 0; i < 3; i++) {} do {} while (false);",
+            r#"{"loopYield": {"strategy": "every", "iterations": 5}}"#,
+        );
+
+        assert!(code.starts_with("let__iterations__=0;"));
+        assert_eq!(
+            code.matches("if(++__iterations__%5===0)awaitnewPromise(self.delay);")
+                .count(),
+            2
+        );
+    }
+
+    #[test]
+    fn loops_wait_once_their_budget_is_spent() {
+        let code = output(
+            "while (false) {}",
+            r#"{"loopYield": {"strategy": "budget", "milliseconds": 16}}"#,
+        );
+
+        assert!(code.starts_with("let__frame__=performance.now();"));
+        assert!(code.contains(
+            "if(performance.now()-__frame__>=16){awaitnewPromise(self.delay);__frame__=performance.now();}"
+        ));
+    }
+
+    #[test]
+    fn turbo_loops_never_wait() {
+        let code = output(
+            "while (false) {}",
+            r#"{"loopYield": {"strategy": "never"}}"#,
+        );
+        assert_eq!(code, "while(false){}");
+    }
+
+    #[test]
+    fn functions_count_their_depth() {
+        let code = output(
+            "function f(n: number): number { return n < 1 ? 0 : f(n - 1); }",
+            r#"{"watchdog": {"maxDepth": 50}}"#,
+        );
+
+        // Top-level functions get the depth of their caller.
+        assert!(code.starts_with(
+            r#"let__depth__=0;asyncfunctionf(self,__depth__,n){if(++__depth__>50)thrownewScrap.StoopError("Maximumcalldepthof50exceededinfunction'f'.");"#
+        ));
+        assert!(code.contains("awaitf(self,__depth__,n-1)"));
+        assert!(!code.contains("finally"));
+    }
+
+    #[test]
+    fn handlers_and_nested_functions_count_their_own_depth() {
+        let code = output(
+            "function f() {} self.whenFlag(() => { const g = () => f(); g(); });",
+            r#"{"watchdog": {"maxDepth": 50}}"#,
+        );
+
+        assert!(code.contains("self.whenFlag(async()=>{let__depth__=0;constg=async()=>{try{"));
+        assert!(code.contains("returnawaitf(self,__depth__);}finally{__depth__--;}"));
+    }
+
+    #[test]
+    fn functions_count_the_iterations_of_their_own_loops() {
+        let code = output(
+            "function f() { while (false) {} } self.whenFlag(() => { while (false) {} });",
+            r#"{"loopYield": {"strategy": "every", "iterations": 5}}"#,
+        );
+
+        assert!(!code.starts_with("let__iterations__=0;"));
+        assert!(code.contains("asyncfunctionf(self){let__iterations__=0;while(false)"));
+        assert!(code.contains("self.whenFlag(async()=>{let__iterations__=0;while(false)"));
+    }
+
+    #[test]
+    fn loops_stop_after_too_many_iterations_without_pausing() {
+        let code = output(
+            "while (true) { self.x += 1; }",
+            r#"{"watchdog": {"maxIterations": 7}, "loopYield": {"strategy": "never"}}"#,
+        );
+
+        assert!(code.contains(
+            r#"{let__steps__=0;while(true){if(++__steps__>7)thrownewScrap.StoopError("Aloopinthemainscriptran7timeswithoutpausing.");"#
+        ));
+    }
+
+    #[test]
+    fn code_without_a_watchdog_is_not_instrumented() {
+        let code = output("function f() { while (true) {} }", "{}");
+
+        assert!(!code.contains("__depth__"));
+        assert!(!code.contains("__steps__"));
+    }
+
+    const HANDLER: &str = "function add(a: number, b: number) {\n\treturn a + b;\n}\n\nself.whenFlag(() => {\n\tif (self.x > 0) {\n\t\tself.move(add(1, 2));\n\t} else {\n\t\tself.say(\"no\");\n\t}\n});\n";
+
+    #[test]
+    fn probes_know_their_position_and_the_variables_in_scope() {
+        let transformed = compile(HANDLER, r#"{"debug": true}"#).unwrap();
+        let code: String = transformed.code.split_whitespace().collect();
+
+        assert!(code.contains(
+            "asyncfunctionadd(self,__caller__,a,b){const__call__={caller:__caller__};awaitself.__probe(0,2,2,()=>({a,b}),__call__);returna+b;}"
+        ));
+        assert!(code.contains("if(self.x>0){awaitself.__probe(3,7,3,()=>({}),__call__);"));
+
+        assert_eq!(transformed.probes.len(), 5);
+        assert_eq!(transformed.probes[0].function.as_deref(), Some("add"));
+        assert_eq!(transformed.probes[3].start.line, 7);
+        assert_eq!(transformed.probes[4].start.line, 9);
+        assert_eq!(transformed.probes[4].function, None);
+    }
+
+    #[test]
+    fn probes_get_the_frame_of_their_call() {
+        let code = output(HANDLER, r#"{"debug": true}"#);
+
+        assert!(code.starts_with("const__call__={caller:null};"));
+        assert!(code.contains("self.whenFlag(async()=>{const__call__={caller:null};"));
+        assert!(code.contains("self.move(awaitadd(self,__call__,1,2));"));
+    }
+
+    #[test]
+    fn top_level_functions_bound_as_values_get_the_frame() {
+        let code = output(
+            "const double = (n: number) => n * 2;\nself.whenKeyPressed(\"space\", double);\n",
+            r#"{"debug": true}"#,
+        );
+
+        assert!(code.contains(
+            "constdouble=async(self,__caller__,n)=>{const__call__={caller:__caller__};returnn*2;};"
+        ));
+        assert!(code.contains("double.bind(null,self,__call__)"));
+    }
+
+    #[test]
+    fn code_without_debugging_has_no_probes() {
+        let transformed = compile(HANDLER, "{}").unwrap();
+
+        assert!(!transformed.code.contains("__probe"));
+        assert!(transformed.probes.is_empty());
+    }
+
+    #[test]
+    fn functions_handlers_and_loops_are_timed() {
+        let code = "function add(a: number, b: number) {\n\treturn a + b;\n}\n\nself.whenFlag(() => {\n\tfor (let i = 0; i < 2; i++) {\n\t\tself.move(add(i, 1));\n\t}\n});\n";
+        let transformed = compile(code, r#"{"profile": true}"#).unwrap();
+        let code: String = transformed.code.split_whitespace().collect();
+
+        assert!(code.contains(
+            "asyncfunctionadd(self,a,b){let__start__=performance.now();try{returna+b;}finally{self.__profile(0,performance.now()-__start__);}}"
+        ));
+        assert!(code.contains("{let__start__=performance.now();try{for(leti=0;"));
+        assert!(code.contains("}finally{self.__profile(1,performance.now()-__start__);}}"));
+        assert!(code.ends_with("}finally{self.__profile(2,performance.now()-__start__);}});"));
+
+        let sites: Vec<_> = transformed
+            .sites
+            .iter()
+            .map(|site| (site.kind, site.name.as_deref(), site.start.line))
+            .collect();
+
+        assert_eq!(
+            sites,
+            [
+                (profiler::Kind::Function, Some("add"), 1),
+                (profiler::Kind::Loop, None, 6),
+                (profiler::Kind::Handler, Some("whenFlag"), 5),
+            ]
+        );
+    }
+
+    #[test]
+    fn statements_and_branches_are_counted() {
+        let code = "if (self.x > 0) {\n\tself.move(1);\n} else {\n\tself.say(\"no\");\n}\nconst a = self.x > 0 ? 1 : 2;\nconst b = self.x > 0 || self.y > 0;\n";
+        let transformed = compile(code, r#"{"coverage": true}"#).unwrap();
+        let code: String = transformed.code.split_whitespace().collect();
+
+        assert!(code.starts_with("self.__cover(0);if(self.x>0){self.__cover(3);self.__cover(1);self.move(1);}else{self.__cover(4);self.__cover(2);"));
+        assert!(code.contains("consta=self.x>0?(self.__cover(6),1):(self.__cover(7),2);"));
+        assert!(code.contains("constb=self.x>0||(self.__cover(9),self.y>0);"));
+
+        let counters: Vec<_> = transformed
+            .counters
+            .iter()
+            .map(|counter| (counter.kind, counter.start.line))
+            .collect();
+
+        assert_eq!(
+            counters,
+            [
+                (coverage::Kind::Statement, 1),
+                (coverage::Kind::Statement, 2),
+                (coverage::Kind::Statement, 4),
+                (coverage::Kind::Branch, 1),
+                (coverage::Kind::Branch, 3),
+                (coverage::Kind::Statement, 6),
+                (coverage::Kind::Branch, 6),
+                (coverage::Kind::Branch, 6),
+                (coverage::Kind::Statement, 7),
+                (coverage::Kind::Branch, 7),
+            ]
+        );
+    }
+
+    #[test]
+    fn counters_know_their_block() {
+        let transformed = compile(
+            "/*@block a1*/\nself.move(1);\nself.turnRight(15);\n",
+            r#"{"coverage": true}"#,
+        )
+        .unwrap();
+
+        assert_eq!(transformed.counters[0].block.as_deref(), Some("a1"));
+    }
+
+    #[test]
+    fn callbacks_without_engine_calls_stay_synchronous() {
+        let code = output(
+            "const xs = [3, 1, 2]; const ys = xs.map(x => x * 2).filter(x => x > 2); xs.sort((a, b) => a - b);",
+            "{}",
+        );
+
+        assert_eq!(
+            code,
+            "constxs=[3,1,2];constys=xs.map((x)=>x*2).filter((x)=>x>2);xs.sort((a,b)=>a-b);"
+        );
+    }
+
+    #[test]
+    fn callbacks_with_engine_calls_become_awaited_loops() {
+        let code = output(
+            "const xs = [1, 2]; const ys = xs.map(x => { self.wait(1); return x; }); xs.forEach(x => self.glide(1, x, 0)); const zs = xs.filter(x => x > 1);",
+            "{}",
+        );
+
+        assert!(code.contains("asyncfunction__map__(array,callback){"));
+        assert!(code.contains("asyncfunction__forEach__(array,callback){"));
+        assert!(!code.contains("asyncfunction__filter__"));
+        assert!(code.contains("constys=await__map__(xs,async(x)=>{awaitself.wait(1);returnx;});"));
+        assert!(code.contains("await__forEach__(xs,async(x)=>awaitself.glide(1,x,0));"));
+        assert!(code.contains("constzs=xs.filter((x)=>x>1);"));
+    }
+
+    #[test]
+    fn callbacks_of_other_objects_stay() {
+        let code = output(
+            "const grid = { map(f: (x: number) => number) { return f(1); } }; const y = grid.map(x => { self.wait(1); return x; });",
+            "{}",
+        );
+
+        assert!(!code.contains("__map__"));
+        assert!(code.contains("consty=awaitgrid.map(async(x)=>{awaitself.wait(1);returnx;});"));
+    }
+
+    #[test]
+    fn functions_of_the_script_are_async_and_get_self() {
+        let code = output(
+            "function walk(steps: number) { self.move(steps); self.wait(1); } self.whenFlag(() => { walk(10); const n = Math.round(self.x); });",
+            "{}",
+        );
+
+        assert!(
+            code.starts_with("asyncfunctionwalk(self,steps){self.move(steps);awaitself.wait(1);}")
+        );
+        assert!(code
+            .contains("self.whenFlag(async()=>{awaitwalk(self,10);constn=Math.round(self.x);});"));
+    }
+
+    #[test]
+    fn methods_are_async_and_awaited() {
+        let code = output(
+            "class Enemy { chase() { self.glide(1, 0, 0); } } const e = new Enemy(); e.chase(); const o = { jump() { self.changeY(10); self.wait(0.5); } }; o.jump();",
+            "{}",
+        );
+
+        assert!(code.contains("classEnemy{asyncchase(){awaitself.glide(1,0,0);}}"));
+        assert!(code.contains("awaite.chase();"));
+        assert!(code.contains("asyncjump(){"));
+        assert!(code.contains("awaito.jump();"));
+    }
+
+    #[test]
+    fn accessors_and_constructors_cant_wait() {
+        for (code, function) in [
+            (
+                "class A { get y() { self.wait(1); return 2; } }",
+                "A getter",
+            ),
+            ("class A { set y(v: number) { self.wait(v); } }", "A setter"),
+            (
+                "class A { constructor() { self.wait(1); } }",
+                "A constructor",
+            ),
+        ] {
+            match compile(code, "{}") {
+                Err(CompileError::Unsupported(diagnostics)) => {
+                    assert!(diagnostics[0].message.starts_with(function), "{}", code)
+                }
+                result => panic!("{}: {:?}", code, result.map(|t| t.code)),
+            }
+        }
+    }
+
+    #[test]
+    fn generators_become_async_generators() {
+        let code = output(
+            "function* count(n: number) { for (let i = 0; i < n; i++) { self.wait(1); yield i; } } for (const i of count(3)) { self.say(i); }",
+            "{}",
+        );
+
+        assert_eq!(
+            code,
+            "asyncfunction*count(self,n){for(leti=0;i<n;i++){awaitnewPromise(self.delay);awaitself.wait(1);yieldi;}}forawait(constiofcount(self,3)){awaitnewPromise(self.delay);self.say(i);}"
+        );
+    }
+
+    #[test]
+    fn setters_get_the_arguments_of_their_tag() {
+        let code = output("self.direction = 90; self.x += 1;", "{}");
+
+        assert_eq!(
+            code,
+            r#"awaitself.pointInDirection(90);awaitself.setX("x",self.x+1);"#
+        );
+    }
+
+    #[test]
+    fn setters_are_only_called_on_sprites_and_the_stage() {
+        let code = output(
+            "const p = { pos: { x: 0 } }; p.pos.x = 5; p.pos.x++; \
+             const e = $.Enemy; e.x = 1;",
+            "{}",
+        );
+
+        assert_eq!(
+            code,
+            r#"constp={pos:{x:0}};p.pos.x=5;p.pos.x++;conste=$.Enemy;awaite.setX("x",1);"#
+        );
+    }
+
+    #[test]
+    fn variables_are_declared_with_their_structured_type() {
+        let code = output(
+            "interface Variables { scores: number[]; mode: \"easy\" | 2; }",
+            "{}",
+        );
+
+        assert_eq!(
+            code,
+            concat!(
+                r#"self.declareVariable("scores",{"element":{"kind":"number"},"kind":"array"},{"optional":false,"readonly":false});"#,
+                r#"self.declareVariable("mode",{"kind":"union","types":[{"kind":"literal","value":"easy"},{"kind":"literal","value":2}]},{"optional":false,"readonly":false});"#
+            )
+        );
+    }
+
+    #[test]
+    fn recovered_parse_errors_are_reported_with_their_position() {
+        let parsed = parse("let a = 1;\nlet b = 08;".to_string()).unwrap();
+
+        assert_eq!(parsed.diagnostics.len(), 1);
+        assert_eq!(parsed.diagnostics[0].severity, Severity::Error);
+        assert_eq!(parsed.diagnostics[0].start.line, 2);
+        assert_eq!(parsed.diagnostics[0].start.column, 9);
+
+        let diagnostics = parse("let a = ;".to_string()).err().unwrap();
+        assert_eq!(diagnostics.last().unwrap().span, (8, 9));
+    }
+
+    #[test]
+    fn syntax_errors_fail_the_transform_with_their_span() {
+        let diagnostics = match compile("self.move(1);\nself.say(", "{}") {
+            Err(CompileError::Parse(diagnostics)) => diagnostics,
+            result => panic!("{:?}", result.map(|transformed| transformed.code)),
+        };
+
+        let last = diagnostics.last().unwrap();
+        assert_eq!(last.start.line, 2);
+    }
+
+    #[test]
+    fn statements_of_blocks_report_that_they_run() {
+        let code = output(
+            "/*@block a1*/\nself.move(1);\nself.turnRight(15);\n",
+            r#"{"highlightBlocks": true}"#,
+        );
+
+        assert!(code.starts_with(r#"self.__running("a1");self.move(1);"#));
+        assert_eq!(code.matches("__running").count(), 1);
+    }
+
+    #[test]
+    fn synchronous_built_ins_are_not_awaited() {
+        let code = output(
+            r#"const r = Math.random(); const s = "a".toUpperCase(); const xs = [1].map(x => x + 1);"#,
+            "{}",
+        );
+
+        assert_eq!(
+            code,
+            r#"constr=Math.random();consts="a".toUpperCase();constxs=[1].map((x)=>x+1);"#
+        );
+    }
+
+    #[test]
+    fn only_top_level_functions_of_the_script_get_self() {
+        let code = output(
+            r#"const n = parseInt("1") + Number(isNaN(2)); function f(x: number) { return x; } f(1); { const f = (x: number) => x; f(2); }"#,
+            "{}",
+        );
+
+        assert!(code.contains(r#"constn=parseInt("1")+Number(isNaN(2));"#));
+        assert!(code.contains("awaitf(self,1);"));
+        // The local `f` shadows the top-level one.
+        assert!(code.contains("constf=async(x)=>x;awaitf(2);"));
+    }
+
+    #[test]
+    fn enums_namespaces_and_parameter_properties_are_lowered() {
+        let code = output(
+            "enum Direction { Up, Down = 5 } namespace Game { export const speed = 2; } class Point { constructor(public x: number) {} } const d = Direction.Down;",
+            "{}",
+        );
+
+        assert!(code.contains(r#"Direction[Direction["Down"]=5]="Down";"#));
+        assert!(code.contains("Game.speed=2;"));
+        assert!(code.contains("constructor(x){this.x=x;}"));
+        assert!(code.contains("constd=5;"));
     }
 }
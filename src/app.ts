@@ -23,6 +23,7 @@ import JSZip from "jszip";
 import Sound from "./components/sounds";
 
 import SB3 from "./code/transformers/sb3";
+import * as SWC from "./utils/swc";
 import Tabs from "./components/tabs";
 
 import * as Blockly from "blockly";
@@ -56,6 +57,11 @@ export class App {
 
 	private scratchFiles = new SB3();
 
+	/**
+	 * Aborts the compilation of the last preview
+	 */
+	private preview?: AbortController;
+
 	start() {
 		this.mode("paced");
 		this.current = new Stage();
@@ -86,17 +92,34 @@ export class App {
 			engine.textContent = engineScript;
 
 			const script = document.createElement("script");
-			let code = "var $ = {};\n\n";
+
+			// The preview still compiling is stale now.
+			this.preview?.abort();
+			const preview = (this.preview = new AbortController());
 
 			try {
-				for (const entity of this.entities) {
-					code += await entity.preview();
-					code += "\n\n";
-				}
+				const bundle = await SWC.compileProject(
+					this.entities.map(entity => entity.preview()),
+					{highlightBlocks: true},
+					preview.signal
+				).catch((errors: SWC.EntityError[] | SWC.Cancelled) => {
+					if (errors instanceof SWC.Cancelled) {
+						throw errors;
+					}
+					throw new Error(SWC.describeErrors(errors));
+				});
 
-				script.textContent = code;
+				script.textContent = bundle.code;
 
 				Object.assign(this.output.contentWindow || {}, {
+					__running: (entity: string, id: string) => {
+						if (
+							this.current.name === entity &&
+							this.tabs.active === this.workspace
+						) {
+							this.workspace.highlight(id);
+						}
+					},
 					alert: (message: string) =>
 						Parley.fire({
 							title: "Project Alert",
@@ -128,6 +151,10 @@ export class App {
 
 				document.body.append(engine, script);
 			} catch (e) {
+				if (e instanceof SWC.Cancelled) {
+					return;
+				}
+
 				await Parley.fire({
 					title: "Runtime Error",
 					body: String(e),
@@ -510,12 +537,13 @@ export class App {
 		zip.file("engine.js", engineScript);
 		zip.file("style.css", engineStyle);
 
-		let scripts = "<script>var $ = {};</script>";
+		const bundle = await SWC.compileProject(
+			this.entities.map(e => e.export(zip))
+		).catch((errors: SWC.EntityError[]) => {
+			throw new Error(SWC.describeErrors(errors));
+		});
 
-		for (const e of this.entities) {
-			await e.export(zip);
-			scripts += `<script src="${e.name}/script.js"></script>`;
-		}
+		zip.file("script.js", bundle.code);
 
 		const index = `
             <!DOCTYPE html>
@@ -527,7 +555,7 @@ export class App {
                 <script src="engine.js"></script>
             </head>
             <body>
-                ${scripts}
+                <script src="script.js"></script>
             </body>
         `;
 
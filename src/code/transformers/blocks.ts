@@ -27,7 +27,7 @@ export default class Blocks {
 	private constructor(readonly workspace: Blockly.Workspace) {}
 
 	static async processEntity(e: Entity) {
-		const tree = await SWC.parse(e.code as string);
+		const {module: tree} = await SWC.parse(e.code as string);
 
 		if (tree) {
 			const parser = new this(e.workspace);
@@ -38,7 +38,7 @@ interface BlockCallback<T extends Blockly.Block = any> {
  * runnable JavaScript, but it is not.
  *
  * 1. ScrapScript must get rid of types.
- * 2. Must go through process in {@link transform javascript.ts}
+ * 2. Must go through process in {@link SWC.compileProject}
  * 3. The code gets warped in a code like:
  * ```js
  * var $ = {};
@@ -55,6 +55,10 @@ interface BlockCallback<T extends Blockly.Block = any> {
 class TypeScript extends Blockly.CodeGenerator {
     static blocks: Record<string, BlockCallback> = {};
 
+    // Prefixes statements with `/*@block id*/` for
+    // SWC.TransformOptions.highlightBlocks and coverage
+    markBlocks = false;
+
     // Directly copied from Blockly's JavaScript generator.
     ORDER_OVERRIDES = [
         // (foo()).bar -> foo().bar
@@ -134,6 +138,10 @@ class TypeScript extends Blockly.CodeGenerator {
         const nextBlock = block.nextConnection && block.nextConnection.targetBlock();
         const nextCode = opt_thisOnly || !block.previousConnection ? "" : this.blockToCode(nextBlock);
 
+        if (this.markBlocks && block.previousConnection) {
+            code = `/*@block ${encodeURIComponent(block.id)}*/\n${code}`;
+        }
+
         return commentCode + code + nextCode;
     }
 
@@ -149,20 +157,24 @@ class TypeScript extends Blockly.CodeGenerator {
         return line + ";";
     }
 
-    async ready(zip?: JSZip) {
-        const code = this.entity.code;
-        const result = await SWC.transform(typeof code === "string" ? code : this.workspaceToCode(this.entity.workspace));
-        const body = this.prefixLines(result, "\t");
-        const isStage = this.entity.isStage();
-        const configuration = {
-            ...this.entity.init,
+    /**
+     * Describes the entity for {@link SWC.compileProject}.
+     * If {@link zip} is provided, the assets get added to it.
+     */
+    source(zip?: JSZip, markBlocks = false): SWC.ProjectEntity {
+        this.markBlocks = markBlocks;
+        const code = typeof this.entity.code === "string" ? this.entity.code : this.workspaceToCode(this.entity.workspace);
+        this.markBlocks = false;
+
+        return {
+            name: this.entity.name,
+            code,
+            stage: this.entity.isStage(),
+            init: this.entity.init,
             current: this.entity.current,
-            images: this.entity.getURLs("costumes", zip),
-            sounds: this.entity.getURLs("sounds", zip),
+            images: Object.entries(this.entity.getURLs("costumes", zip)),
+            sounds: Object.entries(this.entity.getURLs("sounds", zip)),
         };
-        const entity = `$[${JSON.stringify(this.entity.name)}]`;
-        const init = `${entity} = new Scrap.${isStage ? "Stage" : "Sprite"}(${JSON.stringify(configuration, null, "\t")});`;
-        return `${init}\n${entity}.init(async self => {\n${body}});\n${isStage ? "" : `${entity}.addTo($["Stage"])`}\n`;
     }
 
     static register<Block extends Blockly.Block>(...args: [...string[], BlockCallback<Block>]) {
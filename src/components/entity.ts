@@ -119,13 +119,16 @@ export class Entity {
 		);
 	}
 
-	async export(zip: JSZip) {
-		zip = zip.folder(this.name)!;
-		zip.file("script.js", await this.generator.ready(zip));
+	/**
+	 * Adds the assets to the {@link zip}
+	 * and describes the entity with their paths.
+	 */
+	export(zip: JSZip) {
+		return this.generator.source(zip.folder(this.name)!);
 	}
 
-	async preview() {
-		return await this.generator.ready();
+	preview() {
+		return this.generator.source(undefined, true);
 	}
 
 	update() {
@@ -142,7 +145,8 @@ export class Entity {
 	 */
 	async dispose() {
 		if (this.typescript !== undefined) {
-			this.variables = await SWC.getVariables(this.typescript);
+			const {variables} = await SWC.getVariables(this.typescript);
+			this.variables = variables;
 		}
 	}
 
@@ -325,6 +325,15 @@ export default class Workspace implements TabComponent {
 		this.workspace.addChangeListener(this.changed);
 	}
 
+	/**
+	 * Highlights the block that the preview is running.
+	 */
+	highlight(id: string) {
+		if (this.workspace?.getBlockById(id)) {
+			this.workspace.highlightBlock(id);
+		}
+	}
+
 	dispose() {
 		this.container.remove();
 		this.workspace.dispose();
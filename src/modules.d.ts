@@ -28,8 +28,15 @@ declare module app {
     /**
      * Variable tuple.
      * A tuple of a variable name and its type.
+     * Variables from the compiler also carry
+     * their exact, structured type and declaration details.
      */
-    type Variable = [name: string, type: Check];
+    type Variable = [
+        name: string,
+        type: Check,
+        details?: import("./utils/swc").Type,
+        declaration?: import("./utils/swc").Declaration,
+    ];
 }
 
 declare module "*.svg" {
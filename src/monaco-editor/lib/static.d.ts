@@ -60,6 +60,7 @@ interface Stage<Variables = {}, Sound = string> {
     /**
      * Volume of sounds
      * (0-100)
+     * @setter setVolume(name, value)
      */
     volume: number;
 
@@ -122,6 +123,7 @@ interface Stage<Variables = {}, Sound = string> {
 
     /**
      * Pauses the current script
+     * @async
      */
     wait(seconds: number): void;
 
@@ -193,6 +195,7 @@ interface Stage<Variables = {}, Sound = string> {
      * Broadcasts the message to all sprites and the stage
      * and waits for all of them to finish their job.
      * @param message The message to broadcast
+     * @async
      */
     broadcastMessageWait(message: string): void;
 
@@ -220,6 +223,7 @@ interface Stage<Variables = {}, Sound = string> {
      * Switch to backdrop and wait for all listeners to finish executing.
      * Listeners are set by `whenBackdropChangesTo` method.
      * @param name Name of the backdrop
+     * @async
      */
     switchBackdropToWait(name: Backdrop): void;
 
@@ -229,6 +233,7 @@ interface Stage<Variables = {}, Sound = string> {
      * Switch to backdrop and wait for all listeners to finish executing.
      * Listeners are set by `whenBackdropChangesTo` method.
      * @param name Name of the backdrop
+     * @async
      */
     switchBackdropToWait(name: string): void;
 
@@ -260,6 +265,7 @@ interface Stage<Variables = {}, Sound = string> {
     /**
      * Plays the sound until it finishes
      * @param name The sound name
+     * @async
      */
     playSoundUntilDone(name: Sound): void;
 
@@ -336,32 +342,38 @@ interface Sprite<Variables = {}, Sound = string, Costume = string> extends Stage
     /**
      * The horizontal position of the sprite.
      * 0 is the center of the stage.
+     * @setter setX(name, value)
      */
     x: number;
 
     /**
      * The vertical position of the sprite.
      * 0 is the center of the stage.
+     * @setter setY(name, value)
      */
     y: number;
 
     /**
      * Can be dragged by the mouse?
+     * @setter setDraggable(name, value)
      */
     draggable: boolean;
 
     /**
      * The direction the sprite is facing in degrees.
+     * @setter pointInDirection(value)
      */
     direction: number;
 
     /**
      * If the sprite is visible.
+     * @setter setVisible(name, value)
      */
     visible: boolean;
 
     /**
      * The size of the sprite, where 100 is the original size.
+     * @setter setSize(name, value)
      */
     size: number;
 
@@ -392,6 +404,7 @@ interface Sprite<Variables = {}, Sound = string, Costume = string> extends Stage
      * @param seconds time to glide
      * @param x the x coordinate to glide to
      * @param y the y coordinate to glide to
+     * @async
      */
     glide(seconds: number, x: number, y: number): void;
 
@@ -520,6 +533,7 @@ interface Sprite<Variables = {}, Sound = string, Costume = string> extends Stage
      * Shows the content in the sprite's speech bubble and waits for the specified number of seconds.
      * @param contents The content to show. Might be string, number, boolean, or any other type.
      * @param seconds The number of seconds to wait
+     * @async
      */
     thinkWait(contents: any, seconds: number): void;
 
@@ -527,6 +541,7 @@ interface Sprite<Variables = {}, Sound = string, Costume = string> extends Stage
      * Shows the content in the sprite's speech bubble and waits for the specified number of seconds.
      * @param contents The content to show. Might be string, number, boolean, or any other type.
      * @param seconds The number of seconds to wait
+     * @async
      */
     sayWait(contents: any, seconds: number): void;
 
@@ -534,6 +549,7 @@ interface Sprite<Variables = {}, Sound = string, Costume = string> extends Stage
      * Shows the content in the sprite's speech bubble and waits for the user to type the answer.
      * @param contents The content to show. Might be string, number, boolean, or any other type.
      * @returns The answer typed by the user
+     * @async
      */
     ask(contents: any): string;
 
@@ -951,18 +967,21 @@ declare const window: {
     /**
      * Displays the native alert dialog with the specified message.
      * @param message the message to display
+     * @async
      */
     alert(message: string): void;
 
     /**
      * Displays the native confirm dialog with the specified message.
      * @param message the message to display
+     * @async
      */
     confirm(message: string): boolean;
 
     /**
      * 
      * @param message the message to display
+     * @async
      */
     prompt(message: string): string;
 };
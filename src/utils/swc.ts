@@ -13,16 +13,437 @@ import type * as Types from "@swc/types";
 import {Types as types} from "@scrap/blockly";
 import type Nodes from "./nodes";
 
-export function parse(code: string) {
-	return invoke<Types.Module>("parse", {code});
+export interface Position {
+	line: number;
+	column: number;
 }
 
-export function transform(code: string) {
-	return invoke<string>("transform", {code});
+export interface Diagnostic {
+	message: string;
+	severity: "error" | "warning" | "info";
+	span: [start: number, end: number];
+	start: Position;
+	end: Position;
 }
 
-export function getVariables(code: string) {
-	return invoke<app.Variable[]>("variables", {code});
+export interface Parsed {
+	module: Types.Module;
+	diagnostics: Diagnostic[];
+}
+
+/**
+ * Rejection of a request whose signal got aborted
+ */
+export class Cancelled extends Error {
+	name = "Cancelled";
+
+	constructor() {
+		super("The request was cancelled");
+	}
+}
+
+type Failure<E> = {kind: "failed"; error: E} | {kind: "cancelled"};
+
+let requests = 0;
+
+/**
+ * Invokes a cancellable command. Aborting the signal cancels
+ * the request, so that a stale one stops compiling.
+ * @throws The error of the command or {@link Cancelled}
+ */
+async function request<T>(
+	command: string,
+	args: Record<string, unknown>,
+	signal?: AbortSignal
+) {
+	if (signal?.aborted) {
+		throw new Cancelled();
+	}
+
+	const id = ++requests;
+	const cancel = () => invoke("cancel", {request: id});
+	signal?.addEventListener("abort", cancel);
+
+	try {
+		return await invoke<T>(command, {...args, request: id});
+	} catch (failure) {
+		const result = failure as Failure<unknown> | undefined;
+
+		switch (result?.kind) {
+			case "failed":
+				throw result.error;
+			case "cancelled":
+				throw new Cancelled();
+			default:
+				// Rejected by Tauri itself, e.g. invalid arguments
+				throw failure;
+		}
+	} finally {
+		signal?.removeEventListener("abort", cancel);
+	}
+}
+
+/**
+ * Parses ScrapScript. If the code can't be parsed,
+ * the promise gets rejected with {@link Diagnostic}s,
+ * or with {@link Cancelled} once the signal is aborted.
+ */
+export function parse(code: string, signal?: AbortSignal) {
+	return request<Parsed>("parse", {code}, signal);
+}
+
+export interface CompileError {
+	kind: "parse" | "type" | "codegen" | "unsupported" | "internal";
+	diagnostics: Diagnostic[];
+}
+
+export interface TransformOptions {
+	sourceMap?: boolean;
+	fileName?: string;
+	stage?: boolean;
+	/**
+	 * Names of the stage variables
+	 */
+	globals?: string[];
+	/**
+	 * How loops give control back to the engine,
+	 * `{strategy: "always"}` by default
+	 */
+	loopYield?: LoopYield;
+	/**
+	 * Stops runaway recursion and loops with a descriptive error
+	 */
+	watchdog?: {
+		maxDepth?: number;
+		/**
+		 * Iterations without a pause
+		 */
+		maxIterations?: number;
+	};
+	/**
+	 * Awaits `self.__probe(id, line, column, locals, frame)`
+	 * before every statement. The frame is `{caller}`, created
+	 * by every call of a top-level function and every run of
+	 * a handler, so that stepping over and out follows a script.
+	 */
+	debug?: boolean;
+	/**
+	 * Calls `self.__running(blockId)` before
+	 * the statements of every block
+	 */
+	highlightBlocks?: boolean;
+	/**
+	 * Calls `self.__profile(id, milliseconds)` after
+	 * every function, event handler and loop
+	 */
+	profile?: boolean;
+	/**
+	 * Calls `self.__cover(id)` before every
+	 * statement and in every branch
+	 */
+	coverage?: boolean;
+}
+
+export type LoopYield =
+	| {strategy: "always"}
+	| {strategy: "every"; iterations: number}
+	| {strategy: "budget"; milliseconds: number}
+	| {strategy: "never"};
+
+export interface Transformed {
+	code: string;
+	map: string | null;
+	/**
+	 * Warnings and the errors of the type checker,
+	 * other errors reject the promise
+	 */
+	diagnostics: Diagnostic[];
+	/**
+	 * Probed statements, indexed by probe ID
+	 */
+	probes: Probe[];
+	/**
+	 * Profiled functions and loops, indexed by site ID
+	 */
+	sites: Site[];
+	/**
+	 * Statements and branches counted for coverage, indexed by counter ID
+	 */
+	counters: Counter[];
+}
+
+export interface Probe {
+	span: [number, number];
+	start: Position;
+	end: Position;
+	/**
+	 * Enclosing function, `null` for the main
+	 * script or an anonymous function
+	 */
+	function: string | null;
+}
+
+export interface Site {
+	span: [number, number];
+	start: Position;
+	end: Position;
+	kind: "function" | "handler" | "loop";
+	/**
+	 * Function name, event method of a handler
+	 * or the function enclosing a loop
+	 */
+	name: string | null;
+}
+
+/**
+ * Counters collected by `self.__profile`
+ */
+export interface Sample {
+	entity: string;
+	site: number;
+	calls: number;
+	/**
+	 * Milliseconds, including the pauses
+	 */
+	time: number;
+}
+
+export interface Hotspot extends Site {
+	entity: string;
+	calls: number;
+	time: number;
+	/**
+	 * Milliseconds per call
+	 */
+	average: number;
+}
+
+export interface Counter {
+	span: [number, number];
+	start: Position;
+	end: Position;
+	kind: "statement" | "branch";
+	/**
+	 * ID of the block the statement was generated from
+	 */
+	block: string | null;
+}
+
+/**
+ * An entity after a preview session
+ */
+export interface Session {
+	/**
+	 * Code that was transformed
+	 */
+	code: string;
+	counters: Counter[];
+	/**
+	 * Counted by `self.__cover`, indexed by counter ID
+	 */
+	hits: number[];
+}
+
+export interface Line {
+	/**
+	 * Executions of the statements starting
+	 * on the line, `null` if there are none
+	 */
+	hits: number | null;
+	branches: number;
+	/**
+	 * Branches taken at least once
+	 */
+	taken: number;
+}
+
+export interface Coverage {
+	/**
+	 * Indexed by line number minus one
+	 */
+	lines: Line[];
+	/**
+	 * Executions of the generated blocks, by block ID
+	 */
+	blocks: Record<string, number>;
+}
+
+/**
+ * Compiles ScrapScript to JavaScript. If it fails,
+ * the promise gets rejected with {@link CompileError},
+ * or with {@link Cancelled} once the signal is aborted.
+ */
+export function transform(
+	code: string,
+	options?: TransformOptions,
+	signal?: AbortSignal
+) {
+	return request<Transformed>("transform", {code, options}, signal);
+}
+
+/**
+ * Sprite or stage to compile with {@link compileProject}
+ */
+export interface ProjectEntity {
+	name: string;
+	code: string;
+	stage: boolean;
+	/**
+	 * Initial state, e.g. the position and the size
+	 */
+	init: Record<string, unknown>;
+	/**
+	 * Index of the current costume or backdrop
+	 */
+	current: number;
+	/**
+	 * Names and URLs of the costumes or backdrops, in order
+	 */
+	images: [name: string, url: string][];
+	sounds: [name: string, url: string][];
+}
+
+export interface EntityError extends CompileError {
+	entity: string;
+}
+
+export interface Bundle {
+	/**
+	 * Script running the whole project on top of the engine
+	 */
+	code: string;
+	/**
+	 * Index source map of the script, with a section for
+	 * every entity, if {@link TransformOptions.sourceMap} is set
+	 */
+	map: string | null;
+	/**
+	 * Compiled entities by name
+	 */
+	entities: Record<string, Transformed>;
+}
+
+/**
+ * Compiles all the entities and bundles them into one script.
+ * If any of them fails, the promise gets rejected
+ * with an {@link EntityError} for each of them,
+ * or with {@link Cancelled} once the signal is aborted.
+ */
+export function compileProject(
+	entities: ProjectEntity[],
+	options?: TransformOptions,
+	signal?: AbortSignal
+) {
+	return request<Bundle>("compile_project", {entities, options}, signal);
+}
+
+/**
+ * Ranks the profiled sites, hottest first.
+ * @param sites {@link Transformed.sites} of every entity
+ */
+export function profile(sites: Record<string, Site[]>, samples: Sample[]) {
+	return invoke<Hotspot[]>("profile", {sites, samples});
+}
+
+/**
+ * Merges the coverage counters with the code of every entity.
+ */
+export function coverage(entities: Record<string, Session>) {
+	return invoke<Record<string, Coverage>>("coverage", {entities});
+}
+
+export function describe(diagnostics: Diagnostic[]) {
+	return diagnostics
+		.map(({start, message}) => `${start.line}:${start.column} ${message}`)
+		.join("\n");
+}
+
+export function describeErrors(errors: EntityError[]) {
+	return errors
+		.map(({entity, diagnostics}) => `${entity}\n${describe(diagnostics)}`)
+		.join("\n\n");
+}
+
+/**
+ * Type-checks ScrapScript against the engine API.
+ * @param stage Whether the code belongs to the stage
+ * @param globals Names of the stage variables, sprites can use them
+ */
+export function check(code: string, stage: boolean, globals?: string[]) {
+	return invoke<Diagnostic[]>("check", {code, stage, globals});
+}
+
+/**
+ * Structured type of a variable,
+ * the third item of {@link app.Variable}
+ */
+export type Type =
+	| {
+			kind:
+				| "any"
+				| "unknown"
+				| "never"
+				| "void"
+				| "null"
+				| "undefined"
+				| "number"
+				| "string"
+				| "boolean"
+				| "function";
+	  }
+	| {kind: "literal"; value: boolean | number | string}
+	| {kind: "array"; element: Type}
+	| {kind: "tuple"; elements: {type: Type; optional: boolean}[]}
+	| {
+			kind: "object";
+			properties: {
+				name: string;
+				type: Type;
+				optional: boolean;
+				readonly: boolean;
+			}[];
+			index?: Index;
+	  }
+	| {kind: "union"; types: Type[]}
+	| {kind: "reference"; name: string; arguments: Type[]}
+	| {kind: "keyOf"; target: Type};
+
+/**
+ * Modifiers of a variable,
+ * the fourth item of {@link app.Variable}
+ */
+export interface Declaration {
+	optional: boolean;
+	readonly: boolean;
+	/**
+	 * Value of the `@default` JSDoc tag
+	 */
+	default?: unknown;
+}
+
+/**
+ * `[key: string]: T`, which lets any other name through
+ */
+export interface Index {
+	type: Type;
+	readonly: boolean;
+}
+
+export interface Variables {
+	variables: app.Variable[];
+	/**
+	 * Index signature of the `Variables` interface
+	 */
+	index: Index | null;
+}
+
+/**
+ * Variables declared by ScrapScript. If the code can't
+ * be parsed, the promise gets rejected with {@link Diagnostic}s,
+ * or with {@link Cancelled} once the signal is aborted.
+ */
+export function getVariables(code: string, signal?: AbortSignal) {
+	return request<Variables>("variables", {code}, signal);
 }
 
 export function is<K extends keyof Nodes>(